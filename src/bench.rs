@@ -0,0 +1,131 @@
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use regex::Regex;
+
+/// Timings of a single benchmark run, averaged per iteration.
+#[derive(Clone, Copy)]
+pub struct Bench {
+    pub compile: Duration,
+    pub captures_iter: Duration,
+    pub find_iter: Duration,
+    pub is_match: Duration,
+    /// The length of the haystack, in bytes.
+    pub bytes: usize,
+    pub iterations: u32,
+}
+
+impl Bench {
+    /// Times compiling `re` and searching `hay` with it, `iterations` times each.
+    ///
+    /// Each phase stops early once it has run for a quarter of `max_time`, so the
+    /// benchmark can be run from the UI without freezing it for too long.
+    pub fn run(
+        re: &str,
+        hay: &str,
+        iterations: u32,
+        max_time: Duration,
+    ) -> Result<Self, regex::Error> {
+        let budget = max_time / 4;
+        let compiled = Regex::new(re)?;
+
+        let (compile, n) = time(iterations, budget, || {
+            black_box(Regex::new(re).is_ok());
+        });
+        let (captures_iter, m) = time(iterations, budget, || {
+            black_box(compiled.captures_iter(hay).count());
+        });
+        let (find_iter, k) = time(iterations, budget, || {
+            black_box(compiled.find_iter(hay).count());
+        });
+        let (is_match, l) = time(iterations, budget, || {
+            black_box(compiled.is_match(hay));
+        });
+
+        Ok(Self {
+            compile,
+            captures_iter,
+            find_iter,
+            is_match,
+            bytes: hay.len(),
+            iterations: n.min(m).min(k).min(l),
+        })
+    }
+
+    /// Formats the results, comparing them against a `previous` run if there is one.
+    ///
+    /// `is_match` stops at the first match, so only its time is reported rather than a
+    /// throughput.
+    pub fn report(&self, previous: Option<&Bench>) -> Vec<String> {
+        let phases = [
+            ("compile", self.compile, previous.map(|p| p.compile), false),
+            (
+                "captures_iter",
+                self.captures_iter,
+                previous.map(|p| p.captures_iter),
+                true,
+            ),
+            (
+                "find_iter",
+                self.find_iter,
+                previous.map(|p| p.find_iter),
+                true,
+            ),
+            (
+                "is_match",
+                self.is_match,
+                previous.map(|p| p.is_match),
+                false,
+            ),
+        ];
+
+        let mut lines = vec![format!(
+            "{} bytes, {} iterations",
+            self.bytes, self.iterations
+        )];
+        lines.extend(phases.into_iter().map(|(name, now, before, whole_hay)| {
+            let throughput = if whole_hay {
+                format!("{:>10.1} MB/s", throughput(self.bytes, now))
+            } else {
+                String::new()
+            };
+            let comparison = match before {
+                Some(before) if !before.is_zero() => {
+                    let change = (now.as_secs_f64() / before.as_secs_f64() - 1.0) * 100.0;
+                    format!("  (was {before:.2?}, {change:+.1}%)")
+                }
+                _ => String::new(),
+            };
+            format!("{name:<14}{now:>12.2?}{throughput:>15}{comparison}")
+                .trim_end()
+                .to_owned()
+        }));
+        lines
+    }
+}
+
+/// Megabytes searched per second.
+fn throughput(bytes: usize, per_iteration: Duration) -> f64 {
+    if per_iteration.is_zero() {
+        f64::INFINITY
+    } else {
+        bytes as f64 / 1e6 / per_iteration.as_secs_f64()
+    }
+}
+
+/// Runs `f` up to `iterations` times, returning the average duration and how many
+/// iterations actually ran.
+fn time(iterations: u32, budget: Duration, mut f: impl FnMut()) -> (Duration, u32) {
+    let start = Instant::now();
+    let mut ran = 0;
+    while ran < iterations.max(1) {
+        f();
+        ran += 1;
+        if start.elapsed() >= budget {
+            break;
+        }
+    }
+    (start.elapsed() / ran, ran)
+}
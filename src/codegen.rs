@@ -0,0 +1,449 @@
+use std::fmt::Write;
+
+use crate::flags::Flags;
+
+/// The targets the code generator can emit snippets for.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    Go,
+    Grep,
+    Sed,
+    Ripgrep,
+}
+
+impl Language {
+    pub const ALL: [Language; 7] = [
+        Self::Rust,
+        Self::Python,
+        Self::JavaScript,
+        Self::Go,
+        Self::Grep,
+        Self::Sed,
+        Self::Ripgrep,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Rust => "rust",
+            Self::Python => "python",
+            Self::JavaScript => "javascript",
+            Self::Go => "go",
+            Self::Grep => "grep",
+            Self::Sed => "sed",
+            Self::Ripgrep => "ripgrep",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|lang| lang.name() == name)
+    }
+
+    /// The language after this one, wrapping around.
+    pub fn next(&self) -> Self {
+        let idx = Self::ALL.iter().position(|lang| lang == self).unwrap_or(0);
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+}
+
+/// Emits an idiomatic snippet that runs `re` with `flags` over `hay`.
+pub fn generate(lang: Language, re: &str, flags: Flags, hay: &str) -> String {
+    match lang {
+        Language::Rust => rust(re, flags, hay),
+        Language::Python => python(re, flags, hay),
+        Language::JavaScript => javascript(re, flags, hay),
+        Language::Go => go(re, flags, hay),
+        Language::Grep => grep(re, flags, hay),
+        Language::Sed => sed(re, flags, hay),
+        Language::Ripgrep => ripgrep(re, flags, hay),
+    }
+}
+
+fn rust(re: &str, flags: Flags, hay: &str) -> String {
+    let re = flags.apply(re);
+    // Use the least amount of `#`s that still terminates the raw string correctly
+    let hashes = "#".repeat(
+        (0..)
+            .find(|n| !re.contains(&format!("\"{}", "#".repeat(*n))))
+            .unwrap_or(0),
+    );
+    format!(
+        "use regex::Regex;
+
+let re = Regex::new(r{hashes}\"{re}\"{hashes}).unwrap();
+let hay = {hay:?};
+for caps in re.captures_iter(hay) {{
+    println!(\"{{:?}}\", caps);
+}}
+"
+    )
+}
+
+fn python(re: &str, flags: Flags, hay: &str) -> String {
+    let mut names = Vec::new();
+    for (ch, name) in [
+        ('i', "re.IGNORECASE"),
+        ('m', "re.MULTILINE"),
+        ('s', "re.DOTALL"),
+        ('x', "re.VERBOSE"),
+    ] {
+        if flags.get(ch) {
+            names.push(name);
+        }
+    }
+
+    let mut out = String::from("import re\n\n");
+    if flags.swap_greed {
+        out.push_str("# the `U` flag has no Python equivalent, swap the greediness by hand\n");
+    }
+    let args = if names.is_empty() {
+        String::new()
+    } else {
+        format!(", {}", names.join(" | "))
+    };
+    let _ = write!(
+        out,
+        "pattern = re.compile({}{args})
+test_string = {}
+for match in pattern.finditer(test_string):
+    print(match.group(0), match.groups())
+",
+        python_raw_string(re),
+        escaped_string(hay, false),
+    );
+    out
+}
+
+/// A raw string when the pattern can be represented as one, otherwise a regular string.
+fn python_raw_string(s: &str) -> String {
+    // A raw string can't end with an odd number of backslashes
+    let trailing = s.chars().rev().take_while(|ch| *ch == '\\').count();
+    let printable = !s.chars().any(char::is_control);
+    match ['"', '\''].into_iter().find(|quote| !s.contains(*quote)) {
+        Some(quote) if printable && trailing % 2 == 0 => format!("r{quote}{s}{quote}"),
+        _ => escaped_string(s, false),
+    }
+}
+
+/// A double-quoted string literal, escaped the way C-like languages expect.
+fn escaped_string(s: &str, ascii: bool) -> String {
+    let mut out = String::from('"');
+    for ch in s.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if ch.is_control() || (ascii && !ch.is_ascii()) => {
+                if ch.is_ascii() {
+                    let _ = write!(out, "\\x{:02x}", ch as u32);
+                } else {
+                    let mut buf = [0; 2];
+                    for unit in ch.encode_utf16(&mut buf) {
+                        let _ = write!(out, "\\u{unit:04x}");
+                    }
+                }
+            }
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn javascript(re: &str, flags: Flags, hay: &str) -> String {
+    // JavaScript spells named groups `(?<name>...)`
+    let re = re.replace("(?P<", "(?<");
+
+    // The `u` flag makes the pattern match code points, like Rust's, and is required
+    // for `\p{..}`. It also makes escaping characters that have no meaning an error.
+    let mut literal = String::from("/");
+    let (mut escaped, mut in_class) = (false, false);
+    let mut chars = re.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if escaped {
+            escaped = false;
+            match ch {
+                // `\pL` is only written with braces
+                'p' | 'P' if chars.peek().is_some_and(|next| *next != '{') => {
+                    // This unwrap is okay, because we peeked at the next character above
+                    let name = chars.next().unwrap();
+                    let _ = write!(literal, "\\{ch}{{{name}}}");
+                }
+                '-' if in_class => literal.push_str("\\-"),
+                ch if ch.is_ascii_punctuation() && !"^$\\.*+?()[]{}|/".contains(ch) => {
+                    literal.push(ch)
+                }
+                ch => {
+                    literal.push('\\');
+                    literal.push(ch);
+                }
+            }
+            continue;
+        }
+        match ch {
+            '\\' => escaped = true,
+            '/' => literal.push_str("\\/"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '[' => {
+                in_class = true;
+                literal.push(ch);
+            }
+            ']' => {
+                in_class = false;
+                literal.push(ch);
+            }
+            ch => literal.push(ch),
+        }
+    }
+    if escaped {
+        literal.push('\\');
+    }
+    literal.push_str("/gu");
+    for ch in ['i', 'm', 's'] {
+        if flags.get(ch) {
+            literal.push(ch);
+        }
+    }
+
+    let mut out = String::new();
+    for ch in ['x', 'U'] {
+        if flags.get(ch) {
+            let _ = writeln!(out, "// the `{ch}` flag has no JavaScript equivalent");
+        }
+    }
+    let _ = write!(
+        out,
+        "const regex = {literal};
+const str = {};
+for (const match of str.matchAll(regex)) {{
+  console.log(match[0], match.slice(1));
+}}
+",
+        escaped_string(hay, true),
+    );
+    out
+}
+
+fn go(re: &str, flags: Flags, hay: &str) -> String {
+    let mut out = String::new();
+    if flags.ignore_whitespace {
+        out.push_str("// the `x` flag has no Go equivalent\n");
+    }
+    let re = Flags {
+        ignore_whitespace: false,
+        ..flags
+    }
+    .apply(re)
+    .into_owned();
+    let re = if re.contains('`') || re.contains('\r') {
+        escaped_string(&re, false)
+    } else {
+        format!("`{re}`")
+    };
+    let _ = write!(
+        out,
+        "package main
+
+import (
+\t\"fmt\"
+\t\"regexp\"
+)
+
+func main() {{
+\tre := regexp.MustCompile({re})
+\tstr := {}
+\tfor _, match := range re.FindAllStringSubmatch(str, -1) {{
+\t\tfmt.Println(match)
+\t}}
+}}
+",
+        escaped_string(hay, false),
+    );
+    out
+}
+
+/// Quotes `s` for POSIX shells.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn grep(re: &str, flags: Flags, hay: &str) -> String {
+    // PCRE supports the same inline flags
+    format!(
+        "printf '%s\\n' {} | grep -oP {}\n",
+        shell_quote(hay),
+        shell_quote(&flags.apply(re)),
+    )
+}
+
+fn sed(re: &str, flags: Flags, hay: &str) -> String {
+    // POSIX extended regexes have no Perl classes and no group syntax other than plain
+    // parentheses, and the delimiter has to be escaped
+    let mut translated = String::new();
+    let mut notes = Vec::new();
+    let (mut escaped, mut in_class) = (false, false);
+    let mut chars = re.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if escaped {
+            let class = match ch {
+                'd' | 'D' => Some("[:digit:]"),
+                'w' | 'W' => Some("[:alnum:]_"),
+                's' | 'S' => Some("[:space:]"),
+                _ => None,
+            };
+            match (ch, class, in_class) {
+                (ch, Some(class), false) if ch.is_ascii_lowercase() => {
+                    let _ = write!(translated, "[{class}]");
+                }
+                (_, Some(class), false) => {
+                    let _ = write!(translated, "[^{class}]");
+                }
+                (ch, Some(class), true) if ch.is_ascii_lowercase() => translated.push_str(class),
+                // A bracket expression can't exclude a class from what it matches
+                (_, Some(_), true) => {
+                    notes.push(format!("`\\{ch}` in a class is not expressible in sed"));
+                    translated.push('\\');
+                    translated.push(ch);
+                }
+                ('/', _, _) => translated.push_str("\\/"),
+                // Backslashes are literal in bracket expressions, so the character is
+                // put in as is, except where it would mean something else
+                (']' | '-' | '^', _, true) => {
+                    notes.push(format!("`\\{ch}` in a class is not expressible in sed"));
+                    translated.push('\\');
+                    translated.push(ch);
+                }
+                (ch, _, true) if ch.is_ascii_punctuation() => translated.push(ch),
+                (ch, _, _) => {
+                    translated.push('\\');
+                    translated.push(ch);
+                }
+            }
+            escaped = false;
+            continue;
+        }
+        match ch {
+            '\\' => escaped = true,
+            '[' => {
+                in_class = true;
+                translated.push(ch);
+            }
+            ']' => {
+                in_class = false;
+                translated.push(ch);
+            }
+            '(' if !in_class && chars.peek() == Some(&'?') => {
+                chars.next();
+                match chars.peek() {
+                    Some(':') => {
+                        chars.next();
+                        translated.push('(');
+                    }
+                    Some('P' | '<') => {
+                        chars.by_ref().find(|ch| *ch == '>');
+                        translated.push('(');
+                    }
+                    // Not a group sed can express, leave it for the user to adjust
+                    _ => translated.push_str("(?"),
+                }
+            }
+            '/' => translated.push_str("\\/"),
+            ch => translated.push(ch),
+        }
+    }
+    if escaped {
+        translated.push('\\');
+    }
+
+    let mut out = String::new();
+    for ch in ['m', 's', 'x', 'U'] {
+        if flags.get(ch) {
+            let _ = writeln!(out, "# the `{ch}` flag has no sed equivalent");
+        }
+    }
+    for note in notes {
+        let _ = writeln!(out, "# {note}, adjust it by hand");
+    }
+    let modifiers = if flags.case_insensitive { "gI" } else { "g" };
+    let _ = writeln!(
+        out,
+        "printf '%s\\n' {} | sed -E {}",
+        shell_quote(hay),
+        shell_quote(&format!("s/{translated}/[&]/{modifiers}")),
+    );
+    out
+}
+
+fn ripgrep(re: &str, flags: Flags, hay: &str) -> String {
+    // ripgrep uses the same regex engine, so the pattern is passed on as is. Only
+    // multiline searching has to be enabled for `.` to be able to cross lines.
+    let multiline = if flags.dot_matches_new_line { "U" } else { "" };
+    format!(
+        "printf '%s\\n' {} | rg -o{multiline} {}\n",
+        shell_quote(hay),
+        shell_quote(&flags.apply(re)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippet(lang: Language, re: &str) -> String {
+        generate(lang, re, Flags::default(), "hay")
+    }
+
+    #[test]
+    fn rust_raw_string_hashes() {
+        assert!(snippet(Language::Rust, r"\d+").contains(r#"Regex::new(r"\d+")"#));
+        assert!(snippet(Language::Rust, r#""\d""#).contains(r##"Regex::new(r#""\d""#)"##));
+        assert!(snippet(Language::Rust, r##""#"##).contains(r###"Regex::new(r##""#"##)"###));
+    }
+
+    #[test]
+    fn python_quoting() {
+        assert!(snippet(Language::Python, r"\d+").contains(r#"re.compile(r"\d+")"#));
+        assert!(snippet(Language::Python, r#""\d""#).contains(r#"re.compile(r'"\d"')"#));
+        // Neither quote is free, and raw strings can't end with a lone backslash
+        assert!(snippet(Language::Python, r#"'"\d"#).contains(r#"re.compile("'\"\\d")"#));
+        assert!(snippet(Language::Python, r"a\\\").contains(r#"re.compile("a\\\\\\")"#));
+    }
+
+    #[test]
+    fn javascript_literal() {
+        assert!(snippet(Language::JavaScript, r"a/b\/c").contains(r"/a\/b\/c/gu"));
+        assert!(snippet(Language::JavaScript, r"(?P<n>\pL)").contains(r"/(?<n>\p{L})/gu"));
+        // Escaping characters without a meaning is an error with the `u` flag
+        assert!(snippet(Language::JavaScript, r"\-\#[\-]\.").contains(r"/-#[\-]\./gu"));
+        assert!(snippet(Language::JavaScript, "\"`").contains("/\"`/gu"));
+    }
+
+    #[test]
+    fn go_raw_string() {
+        assert!(snippet(Language::Go, r#"\d"+"#).contains(r#"regexp.MustCompile(`\d"+`)"#));
+        assert!(snippet(Language::Go, r"`\d`").contains(r#"regexp.MustCompile("`\\d`")"#));
+    }
+
+    #[test]
+    fn shell_quoting() {
+        assert!(snippet(Language::Grep, r"it's\b").contains(r"grep -oP 'it'\''s\b'"));
+        assert!(snippet(Language::Ripgrep, r"'\d'").contains(r"rg -o ''\''\d'\'''"));
+    }
+
+    #[test]
+    fn sed_perl_classes() {
+        assert!(
+            snippet(Language::Sed, r"[\d\w]+\D\s/")
+                .contains(r"s/[[:digit:][:alnum:]_]+[^[:digit:]][[:space:]]\//[&]/g")
+        );
+        // Backslashes are literal in bracket expressions
+        assert!(snippet(Language::Sed, r"[\.\\]").contains(r"s/[.\]/[&]/g"));
+        let out = snippet(Language::Sed, r"[\W]");
+        assert!(out.starts_with(r"# `\W` in a class is not expressible in sed"));
+    }
+}
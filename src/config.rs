@@ -0,0 +1,349 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Deserializer, de};
+
+use crate::{
+    RE_ROW,
+    codegen::Language,
+    flags::Flags,
+    keymap::{Keymap, KeymapConfig},
+    regex::{Capacity, Limits},
+    theme::{Theme, ThemeConfig},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to read config file `{0}`: {1}")]
+    Read(PathBuf, io::Error),
+    #[error("invalid config file `{0}`: {1}")]
+    Parse(PathBuf, toml::de::Error),
+}
+
+/// The contents of the config file, as written.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct File {
+    theme: ThemeConfig,
+    keys: KeymapConfig,
+    layout: Layout,
+    panels: Panels,
+    defaults: Defaults,
+    sessions: Sessions,
+    library: LibraryFile,
+    cache: Capacity,
+    limits: LimitsFile,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Layout {
+    /// How many rows the test string is below the pattern.
+    #[serde(deserialize_with = "lines_between")]
+    pub lines_between: u16,
+    pub regex_title: String,
+    pub hay_title: String,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            lines_between: 3,
+            regex_title: "REGULAR EXPRESSION: ".to_owned(),
+            hay_title: "TEST STRING       : ".to_owned(),
+        }
+    }
+}
+
+impl Layout {
+    /// The column both fields start at, right after the longer title.
+    pub fn left_padding(&self) -> u16 {
+        let width = |title: &str| title.chars().count() as u16;
+        width(&self.regex_title).max(width(&self.hay_title))
+    }
+
+    /// The row the test string is drawn on.
+    pub fn hay_row(&self) -> u16 {
+        RE_ROW + self.lines_between
+    }
+}
+
+/// Which panels are open on startup.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Panels {
+    pub debugger: bool,
+    #[serde(deserialize_with = "language")]
+    pub code: Option<Language>,
+    pub diagram: bool,
+    pub debug: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Defaults {
+    /// The flags new sessions start with.
+    #[serde(deserialize_with = "flags")]
+    flags: Flags,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Sessions {
+    dir: Option<PathBuf>,
+    autosave: bool,
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            autosave: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct LibraryFile {
+    file: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LimitsFile {
+    size: usize,
+    dfa_size: usize,
+    #[serde(deserialize_with = "budget_ms")]
+    budget_ms: u64,
+}
+
+impl Default for LimitsFile {
+    fn default() -> Self {
+        let limits = Limits::default();
+        Self {
+            size: limits.size_limit,
+            dfa_size: limits.dfa_size_limit,
+            budget_ms: limits.time_budget.as_millis() as u64,
+        }
+    }
+}
+
+impl LimitsFile {
+    fn build(self) -> Limits {
+        Limits {
+            size_limit: self.size,
+            dfa_size_limit: self.dfa_size,
+            time_budget: Duration::from_millis(self.budget_ms),
+        }
+    }
+}
+
+pub struct Config {
+    pub theme: Theme,
+    pub keymap: Keymap,
+    pub layout: Layout,
+    pub panels: Panels,
+    /// The flags new sessions start with.
+    pub flags: Flags,
+    /// Where sessions are saved.
+    pub sessions_dir: PathBuf,
+    /// Whether sessions are saved while they're edited, rather than only on exit.
+    pub autosave: bool,
+    /// The pattern library file.
+    pub library: PathBuf,
+    /// How many patterns and test strings the regex cache keeps.
+    pub cache: Capacity,
+    /// The limits that keep catastrophic patterns from freezing the UI.
+    pub limits: Limits,
+}
+
+impl Config {
+    /// Loads the config file, using the defaults for whatever it doesn't set, or for
+    /// everything if there is no config file.
+    pub fn load() -> Result<Self, Error> {
+        Self::from_path(&base_path("XDG_CONFIG_HOME", "config.toml"))
+    }
+
+    fn from_path(path: &Path) -> Result<Self, Error> {
+        let file = match fs::read_to_string(path) {
+            Ok(s) => toml::from_str(&s).map_err(|err| Error::Parse(path.to_owned(), err))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => File::default(),
+            Err(err) => return Err(Error::Read(path.to_owned(), err)),
+        };
+
+        // https://no-color.org
+        let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        let theme = if no_color {
+            Theme::plain()
+        } else {
+            file.theme.build()
+        };
+
+        let sessions_dir = match file.sessions.dir {
+            Some(dir) => expand_home(dir),
+            None => base_path("XDG_DATA_HOME", "persist"),
+        };
+
+        let library = match file.library.file {
+            Some(file) => expand_home(file),
+            None => base_path("XDG_DATA_HOME", "library.toml"),
+        };
+
+        Ok(Self {
+            theme,
+            keymap: file.keys.build(),
+            layout: file.layout,
+            panels: file.panels,
+            flags: file.defaults.flags,
+            sessions_dir,
+            autosave: file.sessions.autosave,
+            library,
+            cache: file.cache,
+            limits: file.limits.build(),
+        })
+    }
+}
+
+/// `$<var>/replay/<name>` if the XDG base directory variable is set, otherwise
+/// `~/.replay/<name>`. The latter is still used if only it exists, so that what was
+/// saved there before the variable was set doesn't seem to disappear.
+fn base_path(var: &str, name: &str) -> PathBuf {
+    pick_base_path(
+        env::var_os(var).map(PathBuf::from),
+        &home_dir().join(".replay"),
+        name,
+    )
+}
+
+fn pick_base_path(xdg_dir: Option<PathBuf>, legacy_dir: &Path, name: &str) -> PathBuf {
+    let legacy = legacy_dir.join(name);
+    match xdg_dir {
+        // The spec says relative paths are invalid and should be ignored
+        Some(dir) if dir.is_absolute() => {
+            let path = dir.join("replay").join(name);
+            if !path.exists() && legacy.exists() {
+                legacy
+            } else {
+                path
+            }
+        }
+        _ => legacy,
+    }
+}
+
+fn home_dir() -> PathBuf {
+    dirs::home_dir()
+        .with_context(|| "failed to get home dir")
+        .unwrap()
+}
+
+/// Expands a leading `~` to the home directory.
+fn expand_home(path: PathBuf) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => home_dir().join(rest),
+        Err(_) => path,
+    }
+}
+
+fn lines_between<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    // The row right below the pattern is taken by its error messages
+    match u16::deserialize(deserializer)? {
+        lines if lines < 2 => Err(de::Error::custom("`lines_between` must be at least 2")),
+        lines => Ok(lines),
+    }
+}
+
+fn budget_ms<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    // Nothing would ever finish in no time at all
+    match u64::deserialize(deserializer)? {
+        0 => Err(de::Error::custom("`budget_ms` must be at least 1")),
+        budget => Ok(budget),
+    }
+}
+
+fn language<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Language>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    Language::from_name(&name).map(Some).ok_or_else(|| {
+        let names = Language::ALL.map(|lang| lang.name()).join(", ");
+        de::Error::custom(format!(
+            "unknown language `{name}`, expected one of: {names}"
+        ))
+    })
+}
+
+fn flags<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Flags, D::Error> {
+    let letters = String::deserialize(deserializer)?;
+    Flags::parse(&letters).ok_or_else(|| {
+        de::Error::custom(format!(
+            "invalid flags `{letters}`, expected any of: {}",
+            Flags::ALL.iter().collect::<String>()
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<File, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    #[test]
+    fn lines_between_leaves_room_for_errors() {
+        for lines in [0, 1] {
+            let err = parse(&format!("[layout]\nlines_between = {lines}"))
+                .err()
+                .unwrap();
+            assert!(err.to_string().contains("must be at least 2"), "{err}");
+        }
+        let file = parse("[layout]\nlines_between = 2").unwrap();
+        assert_eq!(file.layout.lines_between, 2);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        for s in [
+            "colour = \"red\"",
+            "[layout]\nlines = 3",
+            "[sessions]\ndirectory = \"~/sessions\"",
+            "[limits]\nbudget = 10",
+        ] {
+            let err = parse(s).err().unwrap();
+            assert!(err.to_string().contains("unknown field"), "{err}");
+        }
+    }
+
+    #[test]
+    fn errors_point_at_their_line() {
+        let err = parse("[layout]\nregex_title = \"> \"\nlines_between = 1")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("line 3"), "{err}");
+    }
+
+    #[test]
+    fn legacy_paths_are_used_while_only_they_exist() {
+        let root = env::temp_dir().join(format!("replay-config-{}", std::process::id()));
+        let (xdg, legacy) = (root.join("xdg"), root.join("legacy"));
+        fs::create_dir_all(&legacy).unwrap();
+        let pick = |dir: Option<&Path>| pick_base_path(dir.map(Path::to_owned), &legacy, "persist");
+
+        assert_eq!(pick(None), legacy.join("persist"));
+        assert_eq!(pick(Some(&xdg)), xdg.join("replay").join("persist"));
+        // Relative paths are ignored
+        assert_eq!(pick(Some(Path::new("xdg"))), legacy.join("persist"));
+
+        fs::create_dir(legacy.join("persist")).unwrap();
+        assert_eq!(pick(Some(&xdg)), legacy.join("persist"));
+        fs::create_dir_all(xdg.join("replay").join("persist")).unwrap();
+        assert_eq!(pick(Some(&xdg)), xdg.join("replay").join("persist"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,241 @@
+use std::{collections::HashMap, fmt::Display, time::Duration};
+
+use regex::Regex;
+use regex_automata::{
+    Input,
+    dfa::onepass,
+    hybrid::{self, LazyStateID},
+    nfa::thompson::{self, backtrack::BoundedBacktracker},
+};
+
+use crate::{
+    lru,
+    regex::{GRACE, Limits, Slot},
+};
+
+/// The engine used to resolve capture groups, mirroring the choice made by
+/// `regex_automata::meta`. Finding the bounds of a match is done by the lazy DFA.
+pub enum Engine {
+    OnePass,
+    Backtrack,
+    PikeVm,
+}
+
+impl Display for Engine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::OnePass => "one-pass DFA",
+            Self::Backtrack => "bounded backtracker",
+            Self::PikeVm => "PikeVM",
+        })
+    }
+}
+
+pub enum StepKind {
+    Normal,
+    /// A match ends right before the byte of this step.
+    Match,
+    /// No match can start or continue from here on.
+    Dead,
+    /// The lazy DFA gave up, e.g. on a Unicode word boundary.
+    Quit,
+}
+
+/// A single transition of the lazy DFA.
+pub struct Step {
+    /// Where the search this step is part of started. Like `find_iter`, each search
+    /// after a match starts where the match ended.
+    pub start: usize,
+    pub offset: usize,
+    /// `None` marks the end of the input.
+    pub byte: Option<u8>,
+    /// States are numbered in the order they are first visited.
+    pub state: usize,
+    pub kind: StepKind,
+}
+
+pub struct Report {
+    pub nfa_states: usize,
+    pub dfa_states: usize,
+    pub dfa_memory: usize,
+    pub engine: Engine,
+    pub steps: Vec<Step>,
+}
+
+pub enum Status<'a> {
+    Tracing,
+    Ready(&'a Report),
+    Failed(String),
+}
+
+/// Traces how the pattern is executed on the test string, one transition at a time.
+#[derive(Default)]
+pub struct Debugger {
+    key: Option<(u64, u64)>,
+    /// The trace, which is built in the background since long test strings take a while.
+    report: Option<Slot<Result<Report, String>>>,
+    budget: Duration,
+    step: usize,
+}
+
+impl Debugger {
+    /// Rebuilds the trace if the pattern or the test string have changed.
+    pub fn update(&mut self, re: &Regex, hay: &str, limits: &Limits) {
+        let key = (lru::hash(re.as_str()), lru::hash(hay));
+        if self.key != Some(key) {
+            self.key = Some(key);
+            let (re, hay, limits) = (re.as_str().to_owned(), hay.to_owned(), *limits);
+            let mut slot =
+                Slot::spawn(move || Ok(trace(&re, &hay, &limits).map_err(|err| err.to_string())));
+            slot.wait(GRACE, limits.time_budget);
+            self.report = Some(slot);
+            self.budget = limits.time_budget;
+            self.step = 0;
+        }
+    }
+
+    /// Checks on the trace, returning whether it has finished or ran out of time since
+    /// the last call.
+    pub fn poll(&mut self) -> bool {
+        let budget = self.budget;
+        self.report
+            .as_mut()
+            .is_some_and(|slot| slot.wait(Duration::ZERO, budget))
+    }
+
+    pub fn status(&self) -> Option<Status<'_>> {
+        Some(match self.report.as_ref()? {
+            slot if slot.running() => Status::Tracing,
+            Slot::Queued(_) | Slot::Pending { .. } => {
+                Status::Failed(format!("timed out after {:?}", self.budget))
+            }
+            Slot::Done(Ok(Ok(report))) => Status::Ready(report),
+            Slot::Done(Ok(Err(err))) => Status::Failed(err.clone()),
+            Slot::Done(Err(err)) => Status::Failed(err.to_string()),
+        })
+    }
+
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    pub fn forward(&mut self) -> bool {
+        let len = match self.status() {
+            Some(Status::Ready(report)) => report.steps.len(),
+            _ => 0,
+        };
+        if self.step + 1 < len {
+            self.step += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn back(&mut self) -> bool {
+        if self.step > 0 {
+            self.step -= 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn trace(re: &str, hay: &str, limits: &Limits) -> Result<Report, Box<dyn std::error::Error>> {
+    let nfa = thompson::Compiler::new()
+        .configure(thompson::Config::new().nfa_size_limit(Some(limits.size_limit)))
+        .build(re)?;
+
+    let engine =
+        if nfa.is_always_start_anchored() && onepass::DFA::new_from_nfa(nfa.clone()).is_ok() {
+            Engine::OnePass
+        } else if BoundedBacktracker::new_from_nfa(nfa.clone())
+            .is_ok_and(|backtracker| hay.len() <= backtracker.max_haystack_len())
+        {
+            Engine::Backtrack
+        } else {
+            Engine::PikeVm
+        };
+
+    let dfa = hybrid::dfa::Builder::new()
+        .configure(
+            hybrid::dfa::Config::new()
+                .cache_capacity(limits.dfa_size_limit)
+                .unicode_word_boundary(true),
+        )
+        .build_from_nfa(nfa.clone())?;
+    let mut cache = dfa.create_cache();
+
+    let mut states = HashMap::new();
+    let mut number = |sid: LazyStateID| {
+        let next = states.len();
+        *states.entry(sid).or_insert(next)
+    };
+    let kind = |sid: LazyStateID| {
+        if sid.is_match() {
+            StepKind::Match
+        } else if sid.is_dead() {
+            StepKind::Dead
+        } else if sid.is_quit() {
+            StepKind::Quit
+        } else {
+            StepKind::Normal
+        }
+    };
+
+    let mut steps = Vec::new();
+    let mut start = 0;
+    // Each search goes on until it can't match anymore, and the next one starts where
+    // the last match it saw ended
+    while start <= hay.len() {
+        let first = steps.len();
+        let mut sid = dfa.start_state_forward(&mut cache, &Input::new(hay).range(start..))?;
+        for (offset, &byte) in hay.as_bytes().iter().enumerate().skip(start) {
+            sid = dfa.next_state(&mut cache, sid, byte)?;
+            steps.push(Step {
+                start,
+                offset,
+                byte: Some(byte),
+                state: number(sid),
+                kind: kind(sid),
+            });
+            if sid.is_dead() || sid.is_quit() {
+                break;
+            }
+        }
+        if !sid.is_dead() && !sid.is_quit() {
+            sid = dfa.next_eoi_state(&mut cache, sid)?;
+            steps.push(Step {
+                start,
+                offset: hay.len(),
+                byte: None,
+                state: number(sid),
+                kind: kind(sid),
+            });
+        }
+
+        let end = steps[first..]
+            .iter()
+            .rfind(|step| matches!(step.kind, StepKind::Match))
+            .map(|step| step.offset);
+        start = match end {
+            _ if sid.is_quit() => break,
+            // An empty match is skipped over, so the next search makes progress
+            Some(end) if end == start => hay[start..]
+                .chars()
+                .next()
+                .map_or(hay.len() + 1, |ch| start + ch.len_utf8()),
+            Some(end) => end,
+            None => break,
+        };
+    }
+
+    Ok(Report {
+        nfa_states: nfa.states().len(),
+        dfa_states: states.len(),
+        dfa_memory: cache.memory_usage(),
+        engine,
+        steps,
+    })
+}
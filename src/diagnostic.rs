@@ -0,0 +1,34 @@
+use std::ops::Range;
+
+use crate::flags::Flags;
+
+/// A problem with the pattern, pointing at the part of it that causes it.
+pub struct Diagnostic {
+    /// Byte range in the pattern, if the problem can be pinned down to one.
+    pub span: Option<Range<usize>>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Pins a syntax error of `re` down to its span, with a concise message.
+    pub fn syntax(re: &str, flags: Flags) -> Option<Self> {
+        let (span, message) = match flags.parser().parse(re) {
+            Ok(_) => return None,
+            Err(regex_syntax::Error::Parse(err)) => (*err.span(), err.kind().to_string()),
+            Err(regex_syntax::Error::Translate(err)) => (*err.span(), err.kind().to_string()),
+            Err(err) => return Some(Self::message(err.to_string())),
+        };
+        Some(Self {
+            span: Some(span.start.offset..span.end.offset),
+            message,
+        })
+    }
+
+    /// A problem that isn't tied to a specific part of the pattern.
+    pub fn message(message: String) -> Self {
+        Self {
+            span: None,
+            message,
+        }
+    }
+}
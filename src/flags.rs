@@ -0,0 +1,86 @@
+use std::{borrow::Cow, fmt::Display};
+
+/// The flags of a pattern, named after their inline form (e.g. `(?i)`).
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct Flags {
+    /// `i`: letters match both upper and lower case.
+    pub case_insensitive: bool,
+    /// `m`: `^` and `$` match the beginning and end of lines.
+    pub multi_line: bool,
+    /// `s`: `.` matches `\n`.
+    pub dot_matches_new_line: bool,
+    /// `x`: whitespace is ignored and `#` starts a comment.
+    pub ignore_whitespace: bool,
+    /// `U`: the meaning of `x*` and `x*?` is swapped.
+    pub swap_greed: bool,
+}
+
+impl Flags {
+    pub const ALL: [char; 5] = ['i', 'm', 's', 'x', 'U'];
+
+    fn flag(&mut self, ch: char) -> Option<&mut bool> {
+        match ch {
+            'i' => Some(&mut self.case_insensitive),
+            'm' => Some(&mut self.multi_line),
+            's' => Some(&mut self.dot_matches_new_line),
+            'x' => Some(&mut self.ignore_whitespace),
+            'U' => Some(&mut self.swap_greed),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, ch: char) -> bool {
+        let mut flags = *self;
+        flags.flag(ch).is_some_and(|flag| *flag)
+    }
+
+    /// Toggles the flag named `ch`, returning whether such a flag exists.
+    pub fn toggle(&mut self, ch: char) -> bool {
+        self.flag(ch).map(|flag| *flag = !*flag).is_some()
+    }
+
+    /// Sets the flag named `ch`, returning whether such a flag exists.
+    pub fn set(&mut self, ch: char) -> bool {
+        self.flag(ch).map(|flag| *flag = true).is_some()
+    }
+
+    /// Parses the flags' letters, e.g. `"im"`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut flags = Self::default();
+        s.chars().all(|ch| flags.set(ch)).then_some(flags)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// A parser of patterns that starts out with these flags enabled.
+    pub fn parser(&self) -> regex_syntax::Parser {
+        regex_syntax::ParserBuilder::new()
+            .case_insensitive(self.case_insensitive)
+            .multi_line(self.multi_line)
+            .dot_matches_new_line(self.dot_matches_new_line)
+            .ignore_whitespace(self.ignore_whitespace)
+            .swap_greed(self.swap_greed)
+            .build()
+    }
+
+    /// Prepends the flags to the pattern as an inline flags group, which is how they
+    /// are passed on to the regex engine.
+    pub fn apply<'a>(&self, re: &'a str) -> Cow<'a, str> {
+        if self.is_empty() {
+            Cow::Borrowed(re)
+        } else {
+            Cow::Owned(format!("(?{self}){re}"))
+        }
+    }
+}
+
+impl Display for Flags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for ch in Self::ALL.into_iter().filter(|ch| self.get(*ch)) {
+            write!(f, "{ch}")?;
+        }
+        Ok(())
+    }
+}
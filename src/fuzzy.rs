@@ -0,0 +1,27 @@
+/// Scores how well `query` matches `text` as a subsequence of it, ignoring case, or
+/// returns `None` if it doesn't match. Runs of consecutive characters and characters
+/// at the start of words score higher, so `tdb` ranks "Toggle the debugger" high.
+pub fn score(query: &str, text: &str) -> Option<u32> {
+    let mut query = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut score = 0;
+    let (mut prev, mut prev_matched) = (None, false);
+    for ch in text.chars() {
+        let Some(&wanted) = query.peek() else {
+            break;
+        };
+        let matched = ch.to_lowercase().eq([wanted]);
+        if matched {
+            query.next();
+            score += 1;
+            if prev_matched {
+                score += 2;
+            }
+            if prev.is_none_or(|prev: char| !prev.is_alphanumeric()) {
+                score += 3;
+            }
+        }
+        prev = Some(ch);
+        prev_matched = matched;
+    }
+    query.peek().is_none().then_some(score)
+}
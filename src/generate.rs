@@ -0,0 +1,187 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use regex::Regex;
+use regex_syntax::hir::{Class, Hir, HirKind};
+
+use crate::flags::Flags;
+
+/// How many times a repetition without an upper bound repeats at most past its minimum,
+/// which keeps the samples short.
+const UNBOUNDED_EXTRA: u32 = 3;
+/// How many times a bounded repetition repeats at most past its minimum.
+const BOUNDED_EXTRA: u32 = 5;
+/// How many tries each sample or near miss gets, since some of them turn out to be
+/// duplicates or to fail the assertions in the pattern.
+const TRIES: usize = 8;
+
+/// A string generated from the pattern.
+pub struct Sample {
+    pub text: String,
+    /// Whether it's matched by the pattern, rather than being a near miss.
+    pub matches: bool,
+}
+
+/// Generates up to `count` strings the pattern `re` matches, followed by up to `count`
+/// near misses, which are small edits of them that it doesn't match. `regex` is the
+/// compiled pattern, which they're checked against.
+pub fn samples(re: &str, flags: Flags, regex: &Regex, count: usize) -> Vec<Sample> {
+    let Ok(hir) = flags.parser().parse(re) else {
+        return Vec::new();
+    };
+    let mut rng = Rng::new();
+
+    // Examples are single-line, like the test string
+    let valid = |text: &str| !text.contains(['\n', '\r']);
+    let mut matching: Vec<String> = Vec::new();
+    for _ in 0..count * TRIES {
+        if matching.len() == count {
+            break;
+        }
+        let mut text = String::new();
+        generate(&hir, &mut rng, &mut text);
+        if valid(&text) && !matching.contains(&text) && regex.is_match(&text) {
+            matching.push(text);
+        }
+    }
+
+    let mut near_misses: Vec<String> = Vec::new();
+    for _ in 0..count * TRIES {
+        if near_misses.len() == count || matching.is_empty() {
+            break;
+        }
+        let sample = &matching[rng.below(matching.len())];
+        let text = mutate(sample, &mut rng);
+        if valid(&text) && !near_misses.contains(&text) && !regex.is_match(&text) {
+            near_misses.push(text);
+        }
+    }
+
+    let matching = matching.into_iter().map(|text| Sample {
+        text,
+        matches: true,
+    });
+    let near_misses = near_misses.into_iter().map(|text| Sample {
+        text,
+        matches: false,
+    });
+    matching.chain(near_misses).collect()
+}
+
+fn generate(hir: &Hir, rng: &mut Rng, out: &mut String) {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => {}
+        HirKind::Literal(literal) => out.push_str(&String::from_utf8_lossy(&literal.0)),
+        HirKind::Class(Class::Unicode(class)) => {
+            let ranges: Vec<_> = class
+                .ranges()
+                .iter()
+                .map(|r| (r.start() as u32, r.end() as u32))
+                .collect();
+            if let Some(ch) = pick(&ranges, rng) {
+                out.push(ch);
+            }
+        }
+        HirKind::Class(Class::Bytes(class)) => {
+            // Bytes past ASCII would make the sample invalid UTF-8
+            let ranges: Vec<_> = class
+                .ranges()
+                .iter()
+                .filter(|r| r.start().is_ascii())
+                .map(|r| (r.start() as u32, r.end().min(0x7F) as u32))
+                .collect();
+            if let Some(ch) = pick(&ranges, rng) {
+                out.push(ch);
+            }
+        }
+        HirKind::Repetition(repetition) => {
+            let min = repetition.min;
+            let max = match repetition.max {
+                Some(max) => max.min(min.saturating_add(BOUNDED_EXTRA)),
+                None => min.saturating_add(UNBOUNDED_EXTRA),
+            };
+            let times = min + rng.below((max - min) as usize + 1) as u32;
+            for _ in 0..times {
+                generate(&repetition.sub, rng, out);
+            }
+        }
+        HirKind::Capture(capture) => generate(&capture.sub, rng, out),
+        HirKind::Concat(hirs) => hirs.iter().for_each(|hir| generate(hir, rng, out)),
+        HirKind::Alternation(hirs) => generate(&hirs[rng.below(hirs.len())], rng, out),
+    }
+}
+
+/// Picks a character of the class with the given ranges, preferring printable ASCII,
+/// then anything else that isn't a control character, so that e.g. `\w` or `.` give
+/// readable samples.
+fn pick(ranges: &[(u32, u32)], rng: &mut Rng) -> Option<char> {
+    let clip = |lo: u32, hi: u32| -> Vec<(u32, u32)> {
+        ranges
+            .iter()
+            .filter_map(|&(start, end)| {
+                let (start, end) = (start.max(lo), end.min(hi));
+                (start <= end).then_some((start, end))
+            })
+            .collect()
+    };
+    let printable = clip(0x20, 0x7E);
+    let visible = clip(0xA0, 0x10FFFF);
+    let ranges = [printable, visible, ranges.to_vec()]
+        .into_iter()
+        .find(|ranges| !ranges.is_empty())?;
+
+    let (start, end) = ranges[rng.below(ranges.len())];
+    let code = start + rng.below((end - start) as usize + 1) as u32;
+    // The ranges can span the surrogates, which aren't characters
+    char::from_u32(code).or(char::from_u32(start))
+}
+
+/// A small edit of `sample`: a character deleted, replaced, inserted or doubled, or
+/// the end cut off.
+fn mutate(sample: &str, rng: &mut Rng) -> String {
+    const REPLACEMENTS: [char; 7] = ['a', 'Z', '0', '9', '-', '.', ' '];
+
+    let mut chars: Vec<char> = sample.chars().collect();
+    let at = rng.below(chars.len().max(1));
+    let replacement = REPLACEMENTS[rng.below(REPLACEMENTS.len())];
+    match (rng.below(5), chars.get(at).copied()) {
+        (0, Some(_)) => {
+            chars.remove(at);
+        }
+        (1, Some(ch)) if ch != replacement => chars[at] = replacement,
+        (2, Some(ch)) => chars.insert(at, ch),
+        (3, Some(_)) => chars.truncate(at),
+        _ => chars.insert(at, replacement),
+    }
+    chars.into_iter().collect()
+}
+
+/// A xorshift generator, which is plenty for picking samples.
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        // The state must not be zero
+        Self(seed | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// A number in `0..n`, or 0 if `n` is 0.
+    fn below(&mut self, n: usize) -> usize {
+        if n == 0 {
+            0
+        } else {
+            (self.next() % n as u64) as usize
+        }
+    }
+}
@@ -1,12 +1,13 @@
-use crossterm::style::Color;
-use tree_sitter::Parser;
+use std::ops::Range;
+
+use tree_sitter::{Node, Parser};
 use tree_sitter_highlight::{
     Error, Highlight, HighlightConfiguration, HighlightEvent, Highlighter,
 };
 
-#[derive(PartialOrd, PartialEq, Eq, Ord, Clone)]
+#[derive(PartialOrd, PartialEq, Eq, Ord, Clone, Copy)]
 // Order is Priotity when multiple groups are active
-enum HighlightGroup {
+pub enum HighlightGroup {
     Flags,
     Anchors,
     Quantifiers,
@@ -17,11 +18,10 @@ enum HighlightGroup {
 }
 
 macro_rules! group_data {
-    ($group:ident, $group_name:expr, $color:expr, [$($token:expr),*]) => {
+    ($group:ident, $group_name:expr, [$($token:expr),*]) => {
         HighlightGroupData {
             group: HighlightGroup::$group,
             group_name: $group_name,
-            color: $color,
             query: {
                 const MATCHES: &str = concat!(
                     "[ ",
@@ -32,11 +32,10 @@ macro_rules! group_data {
             }
         }
     };
-    ($group:ident, $group_name:expr, $color:expr) => {
+    ($group:ident, $group_name:expr) => {
         HighlightGroupData {
             group: HighlightGroup::$group,
             group_name: $group_name,
-            color: $color,
             query: None,
         }
     };
@@ -45,7 +44,6 @@ macro_rules! group_data {
 struct HighlightGroupData {
     group: HighlightGroup,
     group_name: &'static str,
-    color: Color,
     query: Option<&'static str>,
 }
 
@@ -53,26 +51,26 @@ impl HighlightGroup {
     fn all() -> &'static [HighlightGroupData] {
         &[
             // if no token used, then the group definition comes from tree_sitter_regex::HIGHLIGHTS_QUERY
-            group_data!(Flags, "flags", Color::Blue, ["flags", "inline_flags_group"]),
-            group_data!(Anchors, "anchors", Color::Magenta, [
+            group_data!(Flags, "flags", ["flags", "inline_flags_group"]),
+            group_data!(Anchors, "anchors", [
                 "start_assertion",
                 "end_assertion",
                 "boundary_assertion",
                 "non_boundary_assertion"
             ]),
-            group_data!(Quantifiers, "quantifiers", Color::DarkMagenta, [
+            group_data!(Quantifiers, "quantifiers", [
                 "one_or_more",
                 "optional",
                 "zero_or_more",
                 "count_quantifier"
             ]),
-            group_data!(CharacterClass, "character_class", Color::DarkBlue, [
+            group_data!(CharacterClass, "character_class", [
                 "character_class_escape",
                 "character_class"
             ]),
-            group_data!(Operator, "operator", Color::DarkYellow),
-            group_data!(Escape, "escape", Color::Black),
-            group_data!(Group, "property", Color::Black),
+            group_data!(Operator, "operator"),
+            group_data!(Escape, "escape"),
+            group_data!(Group, "property"),
         ]
     }
 
@@ -80,10 +78,6 @@ impl HighlightGroup {
         Self::all().iter().find(|x| &x.group == self).unwrap()
     }
 
-    fn color(&self) -> Color {
-        self.data().color
-    }
-
     fn group_name(&self) -> &'static str {
         self.data().group_name
     }
@@ -149,23 +143,19 @@ impl HighlightEventWrapper {
     }
 }
 
+/// Yields the highlight group of each byte of the pattern, if it's in one.
 impl Iterator for HighlightEventWrapper {
-    type Item = Color;
-    fn next(&mut self) -> Option<Color> {
+    type Item = Option<HighlightGroup>;
+    fn next(&mut self) -> Option<Option<HighlightGroup>> {
         if self.pos < self.limit {
             self.pos += 1;
-            return self
-                .stack
-                .iter()
-                .min()
-                .map(|group| group.color())
-                .or(Some(Color::Reset));
+            return Some(self.stack.iter().min().copied());
         }
 
         if let Some(Ok(event)) = self.iter.next() {
             match event {
                 HighlightEvent::HighlightStart(Highlight(num)) => {
-                    self.stack.push(HighlightGroup::all()[num].group.clone());
+                    self.stack.push(HighlightGroup::all()[num].group);
                 }
                 HighlightEvent::Source { start: _, end } => {
                     self.limit = end;
@@ -180,3 +170,144 @@ impl Iterator for HighlightEventWrapper {
         }
     }
 }
+
+/// A parenthesized construct in the pattern, e.g. a group or a lookaround.
+pub struct GroupSpan {
+    /// Byte offset of the `(`.
+    pub open: usize,
+    /// Byte offset of the `)`, if the group is closed.
+    pub close: Option<usize>,
+    /// How many groups enclose this one, starting from 1.
+    pub depth: usize,
+    /// The capture index, for capturing groups.
+    pub index: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct Structure {
+    pub groups: Vec<GroupSpan>,
+    /// Byte ranges of the parts of the pattern that failed to parse.
+    pub errors: Vec<Range<usize>>,
+}
+
+/// Finds the groups of the pattern from its parse tree, so escaped parentheses and
+/// parentheses inside character classes are told apart from actual groups.
+pub fn structure(re: &str) -> Structure {
+    let mut parser = Parser::new();
+    if parser
+        .set_language(&tree_sitter_regex::LANGUAGE.into())
+        .is_err()
+    {
+        return Structure::default();
+    }
+    let Some(tree) = parser.parse(re, None) else {
+        return Structure::default();
+    };
+
+    let mut structure = Structure::default();
+    collect(tree.root_node(), 0, &mut 0, &mut structure);
+    structure
+}
+
+fn collect(node: Node, depth: usize, captures: &mut usize, structure: &mut Structure) {
+    if node.is_error() || node.is_missing() {
+        structure.errors.push(node.start_byte()..node.end_byte());
+    }
+
+    let depth = match node.kind() {
+        "anonymous_capturing_group"
+        | "named_capturing_group"
+        | "non_capturing_group"
+        | "inline_flags_group"
+        | "lookaround_assertion" => {
+            let index = if matches!(
+                node.kind(),
+                "anonymous_capturing_group" | "named_capturing_group"
+            ) {
+                *captures += 1;
+                Some(*captures)
+            } else {
+                None
+            };
+            let close = node
+                .child(node.child_count().saturating_sub(1))
+                .filter(|last| last.kind() == ")" && !last.is_missing())
+                .map(|last| last.start_byte());
+            structure.groups.push(GroupSpan {
+                open: node.start_byte(),
+                close,
+                depth: depth + 1,
+                index,
+            });
+            depth + 1
+        }
+        _ => depth,
+    };
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect(child, depth, captures, structure);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::Theme;
+
+    /// The groups of `re`, as their opening and closing offsets, depth and index.
+    fn groups(re: &str) -> Vec<(usize, Option<usize>, usize, Option<usize>)> {
+        structure(re)
+            .groups
+            .iter()
+            .map(|g| (g.open, g.close, g.depth, g.index))
+            .collect()
+    }
+
+    #[test]
+    fn escaped_parentheses_are_not_groups() {
+        assert_eq!(groups(r"\(a\)"), []);
+        assert_eq!(groups(r"\((b)"), [(2, Some(4), 1, Some(1))]);
+    }
+
+    #[test]
+    fn parentheses_in_classes_are_not_groups() {
+        assert_eq!(groups("[(]a[)]"), []);
+        assert_eq!(groups("[(](b)"), [(3, Some(5), 1, Some(1))]);
+    }
+
+    #[test]
+    fn named_groups_are_numbered_with_the_others() {
+        assert_eq!(
+            groups("(?P<n>a)(?:b)(c)"),
+            [
+                (0, Some(7), 1, Some(1)),
+                (8, Some(12), 1, None),
+                (13, Some(15), 1, Some(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn unbalanced_parentheses_are_errors() {
+        let unclosed = structure("(a");
+        assert_eq!(unclosed.groups.len(), 1);
+        assert_eq!(unclosed.groups[0].close, None);
+        assert!(!unclosed.errors.is_empty());
+
+        assert!(!structure("a)").errors.is_empty());
+        assert!(structure("(a)").errors.is_empty());
+    }
+
+    #[test]
+    fn deep_groups_wrap_around_the_layer_colors() {
+        let re = "((((((a))))))";
+        let depths: Vec<_> = structure(re).groups.iter().map(|g| g.depth).collect();
+        assert_eq!(depths, [1, 2, 3, 4, 5, 6]);
+
+        // The dark theme has five colors for groups, after the one for the match
+        let theme = Theme::dark();
+        assert_eq!(theme.layer(6), theme.layer(1));
+        assert_ne!(theme.layer(5), theme.layer(1));
+    }
+}
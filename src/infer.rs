@@ -0,0 +1,280 @@
+use std::collections::BTreeSet;
+
+use regex::Regex;
+
+use crate::{flags::Flags, persist::Example};
+
+/// A pattern inferred from the examples.
+pub struct Candidate {
+    pub pattern: String,
+    /// How many of the examples it does what's expected with.
+    pub correct: usize,
+}
+
+/// Proposes patterns that match the examples that should match and don't match the
+/// rest, best first. They're anchored, since they're inferred from whole strings.
+pub fn candidates(examples: &[Example], flags: Flags, count: usize) -> Vec<Candidate> {
+    let positives: Vec<_> = examples
+        .iter()
+        .filter(|e| e.should_match)
+        .map(|e| e.text.as_str())
+        .collect();
+    if positives.is_empty() {
+        return Vec::new();
+    }
+
+    // Each pattern comes with its rank among the equally correct ones. The aligned
+    // runs keep the structure of the examples, whereas the affixes match anything
+    // between them, and the literals match nothing else.
+    let mut patterns = Vec::new();
+    for coarse in [false, true] {
+        if let Some(tokens) = align(&positives, coarse) {
+            patterns.push((0, sequence(&tokens, false)));
+            patterns.push((0, sequence(&tokens, true)));
+        }
+    }
+    patterns.extend(affixes(&positives).into_iter().map(|pattern| (1, pattern)));
+    let literals: Vec<_> = positives.iter().map(|p| escape(p)).collect();
+    patterns.push((
+        2,
+        match &literals[..] {
+            [literal] => literal.clone(),
+            literals => format!("(?:{})", literals.join("|")),
+        },
+    ));
+
+    let mut seen = BTreeSet::new();
+    let mut candidates: Vec<_> = patterns
+        .into_iter()
+        .map(|(rank, pattern)| (rank, format!("^{pattern}$")))
+        .filter(|(_, pattern)| seen.insert(pattern.clone()))
+        .filter_map(|(rank, pattern)| {
+            let regex = Regex::new(&flags.apply(&pattern)).ok()?;
+            let correct = examples
+                .iter()
+                .filter(|e| regex.is_match(&e.text) == e.should_match)
+                .count();
+            Some((rank, Candidate { pattern, correct }))
+        })
+        .collect();
+    // Shorter patterns are simpler, and tend to generalize better
+    candidates
+        .sort_by_key(|(rank, c)| (examples.len() - c.correct, *rank, c.pattern.chars().count()));
+    candidates
+        .into_iter()
+        .take(count)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// A run of similar characters, the same in all of the positive examples.
+struct Token {
+    /// The characters of the run in each example.
+    runs: Vec<String>,
+    /// Whether the run is of word characters, rather than of a single other character.
+    word: bool,
+}
+
+/// Splits each example into runs of word characters and runs of other characters, or,
+/// unless `coarse`, into runs of digits, lower case and upper case letters. Returns
+/// the runs if all of the examples have the same kinds of them, in the same order.
+fn align(examples: &[&str], coarse: bool) -> Option<Vec<Token>> {
+    let kind = |ch: char| -> Option<u8> {
+        match ch {
+            _ if coarse && (ch.is_alphanumeric() || ch == '_') => Some(0),
+            _ if ch.is_numeric() => Some(1),
+            _ if ch.is_lowercase() => Some(2),
+            _ if ch.is_uppercase() => Some(3),
+            _ => None,
+        }
+    };
+    let split = |s: &str| -> Vec<(Option<u8>, String)> {
+        let mut runs: Vec<(Option<u8>, String)> = Vec::new();
+        for ch in s.chars() {
+            match runs.last_mut() {
+                // Other characters only run on if they're the same character
+                Some((k, run)) if *k == kind(ch) && (k.is_some() || run.ends_with(ch)) => {
+                    run.push(ch)
+                }
+                _ => runs.push((kind(ch), ch.to_string())),
+            }
+        }
+        runs
+    };
+
+    let splits: Vec<_> = examples.iter().map(|s| split(s)).collect();
+    let first = &splits[0];
+    let aligned = splits.iter().all(|runs| {
+        runs.len() == first.len()
+            && runs
+                .iter()
+                .zip(first)
+                .all(|((k, run), (first_k, first_run))| {
+                    k == first_k && (k.is_some() || run.chars().next() == first_run.chars().next())
+                })
+    });
+    if !aligned {
+        return None;
+    }
+    let tokens = (0..first.len())
+        .map(|i| Token {
+            runs: splits.iter().map(|runs| runs[i].1.clone()).collect(),
+            word: first[i].0.is_some(),
+        })
+        .collect();
+    Some(tokens)
+}
+
+/// The pattern matching the tokens, repeating each between as many times as it does
+/// in the examples, or, if `loose`, any number of times.
+fn sequence(tokens: &[Token], loose: bool) -> String {
+    tokens
+        .iter()
+        .map(|token| {
+            let lengths: Vec<_> = token.runs.iter().map(|run| run.chars().count()).collect();
+            let same = token.runs.len() > 1 && token.runs.iter().all(|run| *run == token.runs[0]);
+            let atom = if token.word && same {
+                // A run that's the same in all of the examples, such as a prefix, is
+                // likely fixed
+                return escape(&token.runs[0]);
+            } else if token.word {
+                class(token.runs.iter().flat_map(|run| run.chars()))
+            } else {
+                // This unwrap is okay, because runs are never empty
+                let ch = token.runs[0].chars().next().unwrap();
+                escape(&ch.to_string())
+            };
+            // Other characters are usually separators, whose count matters
+            format!("{atom}{}", quantifier(&lengths, loose && token.word))
+        })
+        .collect()
+}
+
+/// `prefix<class>{min,max}suffix`, from what all of the examples start and end with.
+fn affixes(examples: &[&str]) -> Vec<String> {
+    let chars: Vec<Vec<char>> = examples.iter().map(|s| s.chars().collect()).collect();
+    let shortest = chars.iter().map(Vec::len).min().unwrap_or(0);
+    let prefix = (0..shortest)
+        .take_while(|&i| chars.iter().all(|c| c[i] == chars[0][i]))
+        .count();
+    let suffix = (0..shortest - prefix)
+        .take_while(|&i| {
+            let last = chars[0][chars[0].len() - 1 - i];
+            chars.iter().all(|c| c[c.len() - 1 - i] == last)
+        })
+        .count();
+    if prefix == 0 && suffix == 0 {
+        return Vec::new();
+    }
+
+    let middles: Vec<&[char]> = chars.iter().map(|c| &c[prefix..c.len() - suffix]).collect();
+    let lengths: Vec<_> = middles.iter().map(|m| m.len()).collect();
+    if lengths.iter().all(|&len| len == 0) {
+        return Vec::new();
+    }
+    let prefix: String = escape(&chars[0][..prefix].iter().collect::<String>());
+    let suffix: String = escape(
+        &chars[0][chars[0].len() - suffix..]
+            .iter()
+            .collect::<String>(),
+    );
+    let all_word = middles
+        .iter()
+        .flat_map(|m| m.iter())
+        .all(|ch| ch.is_alphanumeric() || *ch == '_');
+    let atom = if all_word {
+        class(middles.iter().flat_map(|m| m.iter().copied()))
+    } else {
+        ".".to_owned()
+    };
+    [false, true]
+        .into_iter()
+        .map(|loose| format!("{prefix}{atom}{}{suffix}", quantifier(&lengths, loose)))
+        .collect()
+}
+
+/// Escapes the characters of `s` that have a meaning in patterns, other than the ones
+/// that only have one in classes, such as `-`.
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for ch in s.chars() {
+        if regex_syntax::is_meta_character(ch) && !matches!(ch, '-' | '&' | '~') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// The narrowest common class of the word characters `chars`.
+fn class(chars: impl Iterator<Item = char>) -> String {
+    let chars: BTreeSet<char> = chars.collect();
+    let all = |f: fn(&char) -> bool| chars.iter().all(f);
+    let any = |f: fn(&char) -> bool| chars.iter().any(f);
+    if chars.is_empty() {
+        String::new()
+    } else if all(char::is_ascii_digit) {
+        r"\d".to_owned()
+    } else if all(char::is_ascii_lowercase) {
+        "[a-z]".to_owned()
+    } else if all(char::is_ascii_uppercase) {
+        "[A-Z]".to_owned()
+    } else if all(|ch| ch.is_ascii_digit() || ('a'..='f').contains(ch)) {
+        "[0-9a-f]".to_owned()
+    } else if all(|ch| ch.is_ascii_digit() || ('A'..='F').contains(ch)) {
+        "[0-9A-F]".to_owned()
+    } else if all(char::is_ascii_hexdigit) && any(char::is_ascii_digit) {
+        "[0-9A-Fa-f]".to_owned()
+    } else if all(char::is_ascii_alphabetic) {
+        "[A-Za-z]".to_owned()
+    } else if all(char::is_ascii_alphanumeric) {
+        "[A-Za-z0-9]".to_owned()
+    } else {
+        r"\w".to_owned()
+    }
+}
+
+/// The quantifier repeating as many times as `lengths` do, or, if `loose`, any
+/// number of times.
+fn quantifier(lengths: &[usize], loose: bool) -> String {
+    let min = lengths.iter().copied().min().unwrap_or(0);
+    let max = lengths.iter().copied().max().unwrap_or(0);
+    match (loose, min, max) {
+        (true, 0, _) => "*".to_owned(),
+        (true, _, _) => "+".to_owned(),
+        (false, 1, 1) => String::new(),
+        (false, min, max) if min == max => format!("{{{min}}}"),
+        (false, min, max) => format!("{{{min},{max}}}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infer(positives: &[&str]) -> Vec<String> {
+        let examples: Vec<_> = positives
+            .iter()
+            .map(|text| Example {
+                text: text.to_string(),
+                should_match: true,
+            })
+            .collect();
+        candidates(&examples, Flags::default(), 10)
+            .into_iter()
+            .map(|c| c.pattern)
+            .collect()
+    }
+
+    #[test]
+    fn aligned_patterns_come_first() {
+        let candidates = infer(&["v1.2.3", "v10.0.1"]);
+        assert_eq!(candidates[0], r"^v\d+\.\d+\.\d+$");
+        assert_eq!(candidates.last().unwrap(), r"^(?:v1\.2\.3|v10\.0\.1)$");
+    }
+
+    #[test]
+    fn single_examples_are_generalized() {
+        assert_eq!(infer(&["abc-12"])[0], r"^[a-z]+-\d+$");
+    }
+}
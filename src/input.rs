@@ -1,32 +1,54 @@
+use std::ops::Range;
+
 use crate::Change;
 
 #[derive(Default)]
 pub struct Input {
     pub string: String,
     pub cursor: usize,
+    /// Where the selection started, if there is one. It extends up to the cursor.
+    pub anchor: Option<usize>,
 }
 
-// TODO: add the cursor position to the session
-
 impl From<String> for Input {
     fn from(value: String) -> Self {
         Self {
             string: value,
             cursor: 0,
+            anchor: None,
         }
     }
 }
 
 impl Input {
     pub fn insert(&mut self, ch: char) -> Change {
+        self.delete_selection();
         let index = self.byte_index();
         self.string.insert(index, ch);
         self.move_cursor_right();
         Change::new().cursor().content()
     }
 
+    pub fn insert_str(&mut self, s: &str) -> Change {
+        self.delete_selection();
+        let index = self.byte_index();
+        self.string.insert_str(index, s);
+        self.cursor += s.chars().count();
+        Change::new().cursor().content()
+    }
+
+    /// Replaces the whole content, moving the cursor to its end.
+    pub fn set(&mut self, s: String) -> Change {
+        self.string = s;
+        self.cursor = self.string.chars().count();
+        self.anchor = None;
+        Change::new().cursor().content()
+    }
+
     pub fn delete_char(&mut self) -> Change {
-        if self.cursor > 0 {
+        if self.delete_selection() {
+            Change::new().content().cursor()
+        } else if self.cursor > 0 {
             let before = self.string.chars().take(self.cursor - 1);
             let after = self.string.chars().skip(self.cursor);
 
@@ -40,27 +62,66 @@ impl Input {
     }
 
     pub fn move_cursor_end(&mut self) -> Change {
-        self.cursor = self.string.len();
+        self.anchor = None;
+        self.cursor = self.clamp_cursor(self.string.chars().count());
         Change::new().cursor()
     }
 
     pub fn move_cursor_start(&mut self) -> Change {
+        self.anchor = None;
         self.cursor = 0;
         Change::new().cursor()
     }
 
     pub fn move_cursor_left(&mut self) -> Change {
+        self.anchor = None;
         let cursor_moved_left = self.cursor.saturating_sub(1);
         self.cursor = self.clamp_cursor(cursor_moved_left);
         Change::new().cursor()
     }
 
     pub fn move_cursor_right(&mut self) -> Change {
+        self.anchor = None;
         let cursor_moved_right = self.cursor.saturating_add(1);
         self.cursor = self.clamp_cursor(cursor_moved_right);
         Change::new().cursor()
     }
 
+    /// Moves the cursor to the character at `cursor`, dropping the selection.
+    pub fn place(&mut self, cursor: usize) -> Change {
+        self.anchor = None;
+        self.cursor = self.clamp_cursor(cursor);
+        Change::new().cursor()
+    }
+
+    /// Extends the selection from where the cursor was to the character at `cursor`.
+    pub fn select_to(&mut self, cursor: usize) -> Change {
+        self.anchor.get_or_insert(self.cursor);
+        self.cursor = self.clamp_cursor(cursor);
+        Change::new().cursor()
+    }
+
+    /// The selected characters, if any are.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.anchor?;
+        let range = anchor.min(self.cursor)..anchor.max(self.cursor);
+        (!range.is_empty()).then_some(range)
+    }
+
+    /// Deletes the selected characters, returning whether there were any.
+    fn delete_selection(&mut self) -> bool {
+        let Some(range) = self.selection() else {
+            self.anchor = None;
+            return false;
+        };
+        let before = self.string.chars().take(range.start);
+        let after = self.string.chars().skip(range.end);
+        self.string = before.chain(after).collect();
+        self.cursor = range.start;
+        self.anchor = None;
+        true
+    }
+
     fn clamp_cursor(&self, new_cursor_pos: usize) -> usize {
         new_cursor_pos.clamp(0, self.string.chars().count())
     }
@@ -69,7 +130,7 @@ impl Input {
     ///
     /// Since each character in a string can be contain multiple bytes, it's necessary to calculate
     /// the byte index based on the index of the character.
-    fn byte_index(&self) -> usize {
+    pub fn byte_index(&self) -> usize {
         self.string
             .char_indices()
             .map(|(i, _)| i)
@@ -77,3 +138,19 @@ impl Input {
             .unwrap_or(self.string.len())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_counts_characters() {
+        let mut input = Input::from("äöü".to_owned());
+        input.move_cursor_end();
+        assert_eq!(input.cursor, 3);
+        input.insert('x');
+        assert_eq!(input.string, "äöüx");
+        input.move_cursor_right();
+        assert_eq!(input.cursor, 4);
+    }
+}
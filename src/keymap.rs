@@ -0,0 +1,541 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    str::FromStr,
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{
+    Deserialize, Deserializer,
+    de::{self, SeqAccess, Visitor},
+};
+
+/// Everything keys can be bound to.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveStart,
+    MoveEnd,
+    DeleteChar,
+    SwitchField,
+    /// Toggles the flag with the given letter.
+    ToggleFlag(char),
+    ImportLiteral,
+    CycleCode,
+    CopyCode,
+    ToggleDebugger,
+    StepForward,
+    StepBack,
+    ToggleBench,
+    ToggleDebug,
+    ToggleDiagram,
+    ScrollDiagramUp,
+    ScrollDiagramDown,
+    ScrollDiagramLeft,
+    ScrollDiagramRight,
+    SavePattern,
+    Library,
+    History,
+    OpenSession,
+    ExportSession,
+    GenerateExamples,
+    ClearExamples,
+    AddMatchExample,
+    AddNonMatchExample,
+    Infer,
+    NextLint,
+    ApplyFix,
+    CommandPalette,
+    Help,
+    Exit,
+}
+
+impl Action {
+    pub const ALL: [Action; 39] = [
+        Self::MoveLeft,
+        Self::MoveRight,
+        Self::MoveStart,
+        Self::MoveEnd,
+        Self::DeleteChar,
+        Self::SwitchField,
+        Self::ToggleFlag('i'),
+        Self::ToggleFlag('m'),
+        Self::ToggleFlag('s'),
+        Self::ToggleFlag('x'),
+        Self::ToggleFlag('U'),
+        Self::ImportLiteral,
+        Self::CycleCode,
+        Self::CopyCode,
+        Self::ToggleDebugger,
+        Self::StepForward,
+        Self::StepBack,
+        Self::ToggleBench,
+        Self::ToggleDebug,
+        Self::ToggleDiagram,
+        Self::ScrollDiagramUp,
+        Self::ScrollDiagramDown,
+        Self::ScrollDiagramLeft,
+        Self::ScrollDiagramRight,
+        Self::SavePattern,
+        Self::Library,
+        Self::History,
+        Self::OpenSession,
+        Self::ExportSession,
+        Self::GenerateExamples,
+        Self::ClearExamples,
+        Self::AddMatchExample,
+        Self::AddNonMatchExample,
+        Self::Infer,
+        Self::NextLint,
+        Self::ApplyFix,
+        Self::CommandPalette,
+        Self::Help,
+        Self::Exit,
+    ];
+
+    /// The name the action is referred to by in the config file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MoveLeft => "move-left",
+            Self::MoveRight => "move-right",
+            Self::MoveStart => "move-start",
+            Self::MoveEnd => "move-end",
+            Self::DeleteChar => "delete-char",
+            Self::SwitchField => "switch-field",
+            Self::ToggleFlag('i') => "toggle-case-insensitive",
+            Self::ToggleFlag('m') => "toggle-multi-line",
+            Self::ToggleFlag('s') => "toggle-dot-matches-new-line",
+            Self::ToggleFlag('x') => "toggle-ignore-whitespace",
+            Self::ToggleFlag(_) => "toggle-swap-greed",
+            Self::ImportLiteral => "import-literal",
+            Self::CycleCode => "cycle-code",
+            Self::CopyCode => "copy-code",
+            Self::ToggleDebugger => "toggle-debugger",
+            Self::StepForward => "step-forward",
+            Self::StepBack => "step-back",
+            Self::ToggleBench => "toggle-bench",
+            Self::ToggleDebug => "toggle-debug",
+            Self::ToggleDiagram => "toggle-diagram",
+            Self::ScrollDiagramUp => "scroll-diagram-up",
+            Self::ScrollDiagramDown => "scroll-diagram-down",
+            Self::ScrollDiagramLeft => "scroll-diagram-left",
+            Self::ScrollDiagramRight => "scroll-diagram-right",
+            Self::SavePattern => "save-pattern",
+            Self::Library => "library",
+            Self::History => "history",
+            Self::OpenSession => "open-session",
+            Self::ExportSession => "export-session",
+            Self::GenerateExamples => "generate-examples",
+            Self::ClearExamples => "clear-examples",
+            Self::AddMatchExample => "add-match-example",
+            Self::AddNonMatchExample => "add-non-match-example",
+            Self::Infer => "infer",
+            Self::NextLint => "next-lint",
+            Self::ApplyFix => "apply-fix",
+            Self::CommandPalette => "command-palette",
+            Self::Help => "help",
+            Self::Exit => "exit",
+        }
+    }
+
+    /// What the action does, as shown to the user.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::MoveLeft => "Move the cursor left",
+            Self::MoveRight => "Move the cursor right",
+            Self::MoveStart => "Move the cursor to the start",
+            Self::MoveEnd => "Move the cursor to the end",
+            Self::DeleteChar => "Delete the character before the cursor",
+            Self::SwitchField => "Switch between the pattern and the test string",
+            Self::ToggleFlag('i') => "Toggle the case-insensitive flag (i)",
+            Self::ToggleFlag('m') => "Toggle the multi-line flag (m)",
+            Self::ToggleFlag('s') => "Toggle the dot-matches-new-line flag (s)",
+            Self::ToggleFlag('x') => "Toggle the ignore-whitespace flag (x)",
+            Self::ToggleFlag(_) => "Toggle the swap-greed flag (U)",
+            Self::ImportLiteral => "Import the pattern from a source code literal",
+            Self::CycleCode => "Cycle the code generator's language",
+            Self::CopyCode => "Copy the generated code",
+            Self::ToggleDebugger => "Toggle the debugger",
+            Self::StepForward => "Step the debugger forward",
+            Self::StepBack => "Step the debugger back",
+            Self::ToggleBench => "Toggle the benchmark",
+            Self::ToggleDebug => "Toggle the cache statistics",
+            Self::ToggleDiagram => "Toggle the railroad diagram of the pattern",
+            Self::ScrollDiagramUp => "Scroll the diagram up",
+            Self::ScrollDiagramDown => "Scroll the diagram down",
+            Self::ScrollDiagramLeft => "Scroll the diagram left",
+            Self::ScrollDiagramRight => "Scroll the diagram right",
+            Self::SavePattern => "Save the pattern to the library",
+            Self::Library => "Search the pattern library",
+            Self::History => "Browse and restore earlier versions of the session",
+            Self::OpenSession => "Save the session and open another saved one",
+            Self::ExportSession => "Copy the session as a bundle to share",
+            Self::GenerateExamples => "Generate strings the pattern matches, and near misses",
+            Self::ClearExamples => "Remove all of the session's examples",
+            Self::AddMatchExample => "Add the test string to the examples the pattern should match",
+            Self::AddNonMatchExample => {
+                "Add the test string to the examples the pattern shouldn't match"
+            }
+            Self::Infer => "Infer patterns from the examples",
+            Self::NextLint => "Select the next warning about the pattern",
+            Self::ApplyFix => "Apply the quick fix of the selected warning",
+            Self::CommandPalette => "Open the command palette",
+            Self::Help => "Show the key bindings and the regex reference",
+            Self::Exit => "Exit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Self::MoveLeft => &["left", "ctrl+h"],
+            Self::MoveRight => &["right", "ctrl+l"],
+            Self::MoveStart => &["ctrl+left"],
+            Self::MoveEnd => &["ctrl+right"],
+            Self::DeleteChar => &["backspace"],
+            Self::SwitchField => &["tab", "up", "down", "ctrl+j", "ctrl+k", "ctrl+n", "ctrl+p"],
+            Self::ToggleFlag('i') => &["alt+i"],
+            Self::ToggleFlag('m') => &["alt+m"],
+            Self::ToggleFlag('s') => &["alt+s"],
+            Self::ToggleFlag('x') => &["alt+x"],
+            // Accepted with or without `Shift`
+            Self::ToggleFlag(_) => &["alt+u", "alt+U"],
+            Self::ImportLiteral => &["ctrl+o"],
+            Self::CycleCode => &["ctrl+e"],
+            Self::CopyCode => &["ctrl+y"],
+            Self::ToggleDebugger => &["ctrl+t"],
+            Self::StepForward => &["ctrl+f"],
+            Self::StepBack => &["ctrl+b"],
+            Self::ToggleBench => &["ctrl+r"],
+            Self::ToggleDebug => &["ctrl+d"],
+            Self::ToggleDiagram => &["alt+d"],
+            Self::ScrollDiagramUp => &["alt+up"],
+            Self::ScrollDiagramDown => &["alt+down"],
+            Self::ScrollDiagramLeft => &["alt+left"],
+            Self::ScrollDiagramRight => &["alt+right"],
+            Self::SavePattern => &["ctrl+s"],
+            Self::Library => &["ctrl+g"],
+            Self::History => &["alt+h"],
+            // Rarely used, so they're left to the command palette
+            Self::OpenSession => &[],
+            Self::ExportSession => &[],
+            Self::GenerateExamples => &["alt+e"],
+            // Not bound by default, since it can't be undone
+            Self::ClearExamples => &[],
+            Self::AddMatchExample => &["alt+a"],
+            Self::AddNonMatchExample => &["alt+r"],
+            Self::Infer => &["alt+g"],
+            Self::NextLint => &["alt+n"],
+            Self::ApplyFix => &["alt+f"],
+            // `Ctrl+P` has always switched fields
+            Self::CommandPalette => &["alt+p"],
+            // `?` is a quantifier, so it's typed into the pattern rather than bound
+            Self::Help => &["f1"],
+            Self::Exit => &["esc"],
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Action {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Self::from_name(&name).ok_or_else(|| de::Error::custom(format!("unknown action `{name}`")))
+    }
+}
+
+/// A key along with its modifiers, e.g. `ctrl+t`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        let mut modifiers = event.modifiers;
+        // The case of a character already tells whether `Shift` was held
+        if let KeyCode::Char(_) = event.code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self {
+            code: event.code,
+            modifiers,
+        }
+    }
+}
+
+const NAMED_KEYS: [(&str, KeyCode); 15] = [
+    ("backspace", KeyCode::Backspace),
+    ("enter", KeyCode::Enter),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("delete", KeyCode::Delete),
+    ("esc", KeyCode::Esc),
+    ("space", KeyCode::Char(' ')),
+];
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<_> = s.split('+').collect();
+        // A lone `+`, or one after modifiers such as `ctrl++`
+        if s.ends_with("++") || s == "+" {
+            parts.truncate(parts.len() - 2);
+            parts.push("+");
+        }
+        let Some((key, modifiers)) = parts.split_last() else {
+            return Err(format!("invalid key `{s}`"));
+        };
+
+        let mut key = Self {
+            code: parse_code(key).ok_or_else(|| format!("unknown key `{key}` in `{s}`"))?,
+            modifiers: KeyModifiers::NONE,
+        };
+        for modifier in modifiers {
+            key.modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier `{modifier}` in `{s}`")),
+            };
+        }
+        if let KeyCode::Char(_) = key.code
+            && key.modifiers.contains(KeyModifiers::SHIFT)
+        {
+            return Err(format!(
+                "`{s}` can't be bound, use an uppercase character instead of `shift`"
+            ));
+        }
+        Ok(key)
+    }
+}
+
+fn parse_code(s: &str) -> Option<KeyCode> {
+    let mut chars = s.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(ch));
+    }
+    let lower = s.to_lowercase();
+    if let Some(n) = lower.strip_prefix('f').and_then(|n| n.parse().ok()) {
+        return Some(KeyCode::F(n));
+    }
+    NAMED_KEYS
+        .into_iter()
+        .find(|(name, _)| *name == lower)
+        .map(|(_, code)| code)
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl"),
+            (KeyModifiers::ALT, "alt"),
+            (KeyModifiers::SHIFT, "shift"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{name}+")?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(ch) => write!(f, "{ch}"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            code => match NAMED_KEYS.into_iter().find(|(_, c)| *c == code) {
+                Some((name, _)) => write!(f, "{name}"),
+                None => write!(f, "{code:?}"),
+            },
+        }
+    }
+}
+
+/// One key, or a list of them.
+struct Keys(Vec<Key>);
+
+impl<'de> Deserialize<'de> for Keys {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeysVisitor;
+
+        impl<'de> Visitor<'de> for KeysVisitor {
+            type Value = Keys;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a key such as \"ctrl+t\", or a list of them")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Keys, E> {
+                s.parse().map(|key| Keys(vec![key])).map_err(E::custom)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Keys, A::Error> {
+                let mut keys = Vec::new();
+                while let Some(s) = seq.next_element::<String>()? {
+                    keys.push(s.parse().map_err(de::Error::custom)?);
+                }
+                Ok(Keys(keys))
+            }
+        }
+
+        deserializer.deserialize_any(KeysVisitor)
+    }
+}
+
+/// Which action each key is bound to.
+pub struct Keymap {
+    bindings: HashMap<Key, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut bindings = HashMap::new();
+        for action in Action::ALL {
+            for key in action.default_keys() {
+                // This unwrap is okay, because the default keys are known to be valid
+                bindings.insert(key.parse().unwrap(), action);
+            }
+        }
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    pub fn get(&self, event: KeyEvent) -> Option<Action> {
+        self.bindings.get(&Key::from(event)).copied()
+    }
+
+    /// The keys bound to `action`, in a stable order.
+    pub fn keys(&self, action: Action) -> Vec<Key> {
+        let mut keys: Vec<_> = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(key, _)| *key)
+            .collect();
+        keys.sort_by_cached_key(|key| (key.to_string().len(), key.to_string()));
+        keys
+    }
+
+    /// Binds `action` to `keys` instead of its current keys. The keys are taken away
+    /// from the actions they were bound to.
+    fn bind(&mut self, action: Action, keys: Vec<Key>) {
+        self.bindings.retain(|_, bound| *bound != action);
+        for key in keys {
+            self.bindings.insert(key, action);
+        }
+    }
+}
+
+/// The `[keys]` table of the config file, where each action is bound to its keys.
+#[derive(Deserialize, Default)]
+pub struct KeymapConfig(BTreeMap<Action, Keys>);
+
+impl KeymapConfig {
+    pub fn build(self) -> Keymap {
+        let mut keymap = Keymap::default();
+        for (action, Keys(keys)) in self.0 {
+            keymap.bind(action, keys);
+        }
+        keymap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(s: &str) -> Key {
+        s.parse().unwrap()
+    }
+
+    fn event(s: &str) -> KeyEvent {
+        let key = key(s);
+        KeyEvent::new(key.code, key.modifiers)
+    }
+
+    /// The keys bound to `action`, as they're written.
+    fn keys(keymap: &Keymap, action: Action) -> Vec<String> {
+        keymap.keys(action).iter().map(Key::to_string).collect()
+    }
+
+    #[test]
+    fn keys_round_trip() {
+        for s in [
+            "a",
+            "U",
+            "+",
+            "ctrl++",
+            "ctrl+p",
+            "alt+U",
+            "ctrl+alt+delete",
+            "shift+tab",
+            "space",
+            "f1",
+            "f12",
+            "pagedown",
+        ] {
+            assert_eq!(key(s).to_string(), s);
+        }
+        // Modifiers and key names are case-insensitive, and always written the same way
+        assert_eq!(key("Alt+Ctrl+Enter").to_string(), "ctrl+alt+enter");
+        assert_eq!(key(" ").to_string(), "space");
+    }
+
+    #[test]
+    fn invalid_keys_are_rejected() {
+        for s in ["", "ctrl+", "hyper+a", "ctrl+nope", "shift+a", "f1x"] {
+            assert!(s.parse::<Key>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn default_keys_are_valid_and_unique() {
+        let keymap = Keymap::default();
+        for action in Action::ALL {
+            for s in action.default_keys() {
+                assert!(keymap.get(event(s)) == Some(action), "{s}");
+            }
+        }
+    }
+
+    #[test]
+    fn configured_keys_replace_the_defaults() {
+        let config: KeymapConfig = toml::from_str(
+            r#"
+            exit = "ctrl+q"
+            help = ["f1", "ctrl+p"]
+            "#,
+        )
+        .unwrap();
+        let keymap = config.build();
+
+        assert_eq!(keys(&keymap, Action::Exit), ["ctrl+q"]);
+        assert!(keymap.get(event("esc")).is_none());
+        // Keys are taken away from the actions they were bound to
+        assert_eq!(keys(&keymap, Action::Help), ["f1", "ctrl+p"]);
+        assert!(!keys(&keymap, Action::SwitchField).contains(&"ctrl+p".to_owned()));
+        assert_eq!(keys(&keymap, Action::Infer), ["alt+g"]);
+    }
+
+    #[test]
+    fn invalid_key_config_is_rejected() {
+        for config in [
+            r#"no-such-action = "ctrl+q""#,
+            r#"exit = "ctrl+nope""#,
+            "exit = 1",
+        ] {
+            assert!(toml::from_str::<KeymapConfig>(config).is_err(), "{config}");
+        }
+    }
+}
@@ -1,45 +1,98 @@
-use std::io;
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use bench::Bench;
+use codegen::Language;
+use config::{Config, Layout};
 
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
     style::Color,
-    terminal::DisableLineWrap,
+    terminal::{self, DisableLineWrap},
 };
+use debugger::{Debugger, Status as DebuggerStatus};
+use diagnostic::Diagnostic;
+use generate::Sample;
+use infer::Candidate;
 use input::Input;
-use persist::Session;
-use regex::Cache as RegexCache;
-use render::Render;
+use keymap::{Action, Keymap};
+use library::{Library, Pattern};
+use lint::Lint;
+use overlay::{Item, Kind, Overlay};
+use persist::{Access, Example, Resolution, Session, SessionName, Snapshot, Workspace};
+use railroad::View;
+use regex::{Cache as RegexCache, Capture, GRACE, Slot, Status};
+use render::{Emphasis, Render};
 
+pub mod bench;
+pub mod codegen;
+pub mod config;
+mod debugger;
+mod diagnostic;
+pub mod flags;
+mod fuzzy;
+mod generate;
 mod highlight;
+mod infer;
 mod input;
+mod keymap;
+pub mod library;
+mod lint;
+mod literal;
+mod lru;
+mod overlay;
 pub mod persist;
+mod railroad;
+mod reference;
 mod regex;
 mod render;
+mod simplify;
+mod theme;
 
-const LINES_BETWEEN: u16 = 3;
+/// The row the pattern is drawn on. The test string's row depends on the layout.
+const RE_ROW: u16 = 2;
 
-const RE_TITLE: &str = "REGULAR EXPRESSION: ";
-const HAY_TITLE: &str = "TEST STRING       : ";
+/// Where overlays such as the command palette are drawn, and how many entries they show.
+const OVERLAY_ROW: u16 = 1;
+const OVERLAY_ENTRIES: usize = 12;
 
-const LEFT_PADDING: u16 = max(RE_TITLE.len(), HAY_TITLE.len()) as u16;
+/// How many characters a turn of the mouse wheel scrolls the test string by.
+const SCROLL_STEP: usize = 4;
 
-const LAYER_COLORS: [Color; 6] = [
-    Color::Grey, // marks the main match itself
-    Color::Green,
-    Color::Yellow,
-    Color::Blue,
-    Color::Magenta,
-    Color::Cyan,
-];
+/// How many matching strings are generated from the pattern, and how many near misses.
+const SAMPLES: usize = 8;
 
-const fn max(a: usize, b: usize) -> usize {
-    [a, b][(a < b) as usize]
-}
+/// How many patterns inferred from the examples are proposed at most.
+const CANDIDATES: usize = 8;
+
+/// How many rows the railroad diagram shows at least, even if it goes off screen.
+const DIAGRAM_MIN_ROWS: usize = 3;
+
+/// How many times the in-app benchmark runs each phase, and for how long at most.
+const BENCH_ITERATIONS: u32 = 100;
+const BENCH_MAX_TIME: Duration = Duration::from_secs(2);
+
+/// How often to check on background work while waiting for input.
+const TICK: Duration = Duration::from_millis(50);
 
+/// An edited session is saved once no key has been pressed for `AUTOSAVE_IDLE`, or
+/// every `AUTOSAVE_INTERVAL` while it's being edited.
+const AUTOSAVE_IDLE: Duration = Duration::from_secs(2);
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A capture as it's drawn, spanning characters rather than bytes.
 struct Group {
     start: usize,
     end: usize,
     layer: usize,
+    /// The capture group's index, where 0 is the whole match.
+    group: usize,
 }
 
 enum Field {
@@ -80,35 +133,150 @@ impl Default for Change {
 pub struct App<W: io::Write> {
     session: Session,
     render: Render<W>,
+    keymap: Keymap,
+    layout: Layout,
     field: Field,
     regex_cache: RegexCache,
+    /// The last pattern that compiled, whose matches are shown while the pattern is invalid.
+    last_valid: Option<String>,
+    debugger: Option<Debugger>,
+    /// The report of the last benchmark, while its panel is open.
+    bench: Option<Vec<String>>,
+    /// The benchmark running in the background, whose report replaces `bench` once it's done.
+    benching: Option<Slot<Bench>>,
+    /// The language of the generated code, while its panel is open.
+    code: Option<Language>,
+    /// The scrolling of the railroad diagram, while its panel is open.
+    diagram: Option<View>,
+    /// How many characters the test string is scrolled to the left by.
+    hay_scroll: usize,
+    /// The capture that was clicked on, whose details are shown.
+    details: Option<Capture>,
+    overlay: Option<Overlay>,
+    library: Library,
+    /// A message shown next to the session name until the next key is pressed.
+    message: Option<String>,
+    autosave: bool,
+    /// The session as it was last saved, to tell whether it needs saving.
+    saved: Snapshot,
+    last_save: Instant,
+    last_input: Instant,
+    /// The session's history, while it's browsed.
+    history: Vec<Snapshot>,
+    /// The strings generated from the pattern, while they're browsed.
+    samples: Vec<Sample>,
+    /// The patterns inferred from the examples, while they're browsed.
+    candidates: Vec<Candidate>,
+    /// The names of the saved sessions, while they're browsed.
+    sessions: Vec<String>,
+    /// The session as changed by someone else, while the user decides what to do.
+    conflict: Option<Session>,
+    /// Whether to exit once the conflict is resolved.
+    exit_pending: bool,
+    last_check: Instant,
+    /// The warnings about the pattern, as of the last edit.
+    lints: Vec<Lint>,
+    /// The hashes of the pattern and the test string the lints are for.
+    linted: Option<(u64, u64)>,
+    /// The suggestions of simpler ways to write the pattern, while they're worked out.
+    suggestions: Option<Slot<Vec<Lint>>>,
+    /// The warning that is underlined in the pattern, and whose fix is applied.
+    lint: usize,
+    debug: bool,
     exit: bool,
 }
 
 impl<W: io::Write> App<W> {
-    pub fn new(w: W, session: Session) -> Self {
-        Self {
+    pub fn new(w: W, session: Session, library: Library, config: Config) -> Self {
+        let saved = session.snapshot();
+        // Sessions that were saved with their UI state reopen as they were left, and
+        // the rest open with the configured panels
+        let workspace = session.workspace.unwrap_or(Workspace {
+            debugger: config.panels.debugger,
+            code: config.panels.code,
+            diagram: config.panels.diagram,
+            debug: config.panels.debug,
+            ..Workspace::default()
+        });
+        let mut app = Self {
             session,
-            render: Render::new(w),
+            render: Render::new(w, config.theme),
+            keymap: config.keymap,
+            layout: config.layout,
             field: Field::RegexQuery,
-            regex_cache: RegexCache::new(),
+            regex_cache: RegexCache::new(config.cache, config.limits),
+            last_valid: None,
+            debugger: None,
+            bench: None,
+            benching: None,
+            code: None,
+            diagram: None,
+            hay_scroll: 0,
+            details: None,
+            overlay: None,
+            library,
+            message: None,
+            autosave: config.autosave,
+            saved,
+            last_save: Instant::now(),
+            last_input: Instant::now(),
+            history: Vec::new(),
+            samples: Vec::new(),
+            candidates: Vec::new(),
+            sessions: Vec::new(),
+            conflict: None,
+            exit_pending: false,
+            last_check: Instant::now(),
+            lints: Vec::new(),
+            linted: None,
+            suggestions: None,
+            lint: 0,
+            debug: false,
             exit: false,
-        }
+        };
+        app.load_workspace(workspace);
+        app
+    }
+
+    /// Restores the state of the UI saved with the session.
+    fn load_workspace(&mut self, workspace: Workspace) {
+        self.field = if workspace.hay_focused {
+            Field::TestString
+        } else {
+            Field::RegexQuery
+        };
+        self.debugger = workspace.debugger.then(Debugger::default);
+        // The last results are shown, rather than running the benchmark on startup
+        self.bench = self
+            .session
+            .bench
+            .filter(|_| workspace.bench)
+            .map(|bench| bench.report(None));
+        self.code = workspace.code;
+        self.diagram = workspace.diagram.then(View::default);
+        self.hay_scroll = workspace.hay_scroll;
+        self.details = workspace
+            .selected
+            .map(|(group, start, end)| Capture { group, start, end });
+        self.debug = workspace.debug;
     }
 
     pub fn run(mut self) -> io::Result<Session> {
         let mut change = Change::new().cursor().content();
         self.render.queue(DisableLineWrap)?;
+        self.render.queue(EnableBracketedPaste)?;
+        self.render.queue(EnableMouseCapture)?;
 
         while !self.exit {
+            // The cursor position affects the highlighting too (e.g. of the parenthesis
+            // matching the one under it), so moving it redraws the screen as well
             if change.content {
-                self.draw()?;
-            }
-            if change.cursor {
-                let (col, row) = self.pos();
-                self.render.move_to(col, row + 2)?;
+                self.update_lints();
             }
             if change.content || change.cursor {
+                self.draw()?;
+                let (col, row) = self.pos();
+                self.render.move_to(col, row)?;
                 self.render.flush()?;
             }
 
@@ -116,79 +284,967 @@ impl<W: io::Write> App<W> {
         }
 
         // clear the screen after exiting
+        self.render.queue(DisableBracketedPaste)?;
+        self.render.queue(DisableMouseCapture)?;
         self.render.move_to(0, 0)?;
         self.render.clear()?;
         self.render.flush()?;
 
+        self.session.workspace = Some(self.workspace());
         Ok(self.session)
     }
 
+    /// Lints the pattern, unless neither it nor the test string changed since it was
+    /// last linted. Lints only make sense for patterns that compile.
+    fn update_lints(&mut self) {
+        let pattern = self.session.pattern();
+        let key = (
+            lru::hash(&pattern),
+            lru::hash(&self.session.test_string.string),
+        );
+        if self.linted == Some(key) {
+            return;
+        }
+        // Suggestions for the previous version are of no use anymore
+        self.suggestions = None;
+        let limits = *self.regex_cache.limits();
+        let Some(re) = self.regex_cache.compile(&pattern) else {
+            // Linted once it compiles, which the background work is polled for
+            self.linted = None;
+            self.lints = Vec::new();
+            return;
+        };
+
+        let (query, hay, flags) = (
+            &self.session.regex_query.string,
+            &self.session.test_string.string,
+            self.session.flags,
+        );
+        self.lints = lint::lint(query, flags, Some(re), hay);
+        self.lint = self.lint.min(self.lints.len().saturating_sub(1));
+
+        // Each suggestion compiles and runs a rewritten pattern, which may take a while
+        let (query, hay, re) = (query.clone(), hay.clone(), re.clone());
+        let mut slot =
+            Slot::spawn(move || Ok(simplify::suggestions(&query, flags, &re, &hay, &limits)));
+        slot.wait(GRACE, limits.time_budget);
+        self.suggestions = Some(slot);
+        self.poll_suggestions();
+        self.linted = Some(key);
+    }
+
+    /// Adds the suggestions to the lints once they're ready, returning whether they
+    /// were added. Suggestions that run out of time are dropped.
+    fn poll_suggestions(&mut self) -> bool {
+        let budget = self.regex_cache.limits().time_budget;
+        let Some(slot) = &mut self.suggestions else {
+            return false;
+        };
+        slot.wait(Duration::ZERO, budget);
+        let Some(Slot::Done(Ok(suggestions))) = self.suggestions.take_if(|slot| !slot.running())
+        else {
+            return false;
+        };
+        if suggestions.is_empty() {
+            return false;
+        }
+        // Sorting the suggestions in shouldn't change which lint is selected
+        let selected = self.lints.get(self.lint).map(|lint| lint.span.clone());
+        self.lints.extend(suggestions);
+        self.lints
+            .sort_by_key(|lint| (lint.span.start, lint.span.end));
+        self.lint = selected
+            .and_then(|span| self.lints.iter().position(|lint| lint.span == span))
+            .unwrap_or(0);
+        true
+    }
+
+    /// The state of the UI, to be saved with the session.
+    fn workspace(&self) -> Workspace {
+        Workspace {
+            hay_focused: matches!(self.field, Field::TestString),
+            hay_scroll: self.hay_scroll,
+            debugger: self.debugger.is_some(),
+            bench: self.bench.is_some(),
+            code: self.code,
+            diagram: self.diagram.is_some(),
+            debug: self.debug,
+            selected: self
+                .details
+                .map(|Capture { group, start, end }| (group, start, end)),
+        }
+    }
+
     fn draw(&mut self) -> io::Result<()> {
         self.render.clear()?;
 
-        self.render.at(Color::Grey, &self.session.name, 0, 0)?;
+        let (muted, error) = (self.render.theme().muted, self.render.theme().error);
+        self.render.at(muted, &self.session.name, 0, 0)?;
+        if !self.session.flags.is_empty() {
+            self.render
+                .draw(muted, format_args!("  flags: {}", self.session.flags))?;
+        }
+        match self.session.access {
+            Access::ReadWrite => {}
+            Access::ReadOnly => self.render.draw(error, "  [read-only]")?,
+            Access::Locked => self
+                .render
+                .draw(error, "  [read-only: open in another replay]")?,
+        }
+        if let Some(message) = &self.message {
+            let accent = self.render.theme().accent;
+            self.render.draw(accent, format_args!("  {message}"))?;
+        }
 
-        self.render.at(Color::Reset, RE_TITLE, 0, 2)?;
+        let (padding, hay_row) = (self.layout.left_padding(), self.layout.hay_row());
         self.render
-            .at(Color::Reset, HAY_TITLE, 0, LINES_BETWEEN + 2)?;
-
+            .at(Color::Reset, &self.layout.regex_title, 0, RE_ROW)?;
         self.render
-            .draw_regex_query(&self.session.regex_query.string, LEFT_PADDING, 2)?;
-        self.draw_hay(LEFT_PADDING, LINES_BETWEEN + 2)
+            .at(Color::Reset, &self.layout.hay_title, 0, hay_row)?;
+
+        let emphasis = match self.field {
+            Field::RegexQuery => self
+                .group_at_regex_cursor()
+                .map_or(Emphasis::None, Emphasis::Group),
+            Field::TestString => Emphasis::None,
+        };
+        self.render.set_scroll(self.hay_scroll);
+        let (error, lit) = self.draw_hay(emphasis, padding, hay_row)?;
+        let diagnostic = error.map(|error| {
+            Diagnostic::syntax(&self.session.regex_query.string, self.session.flags)
+                // Errors that aren't syntax errors, e.g. exceeding the size limit
+                .unwrap_or_else(|| Diagnostic::message(error))
+        });
+
+        let cursor = match self.field {
+            Field::RegexQuery => Some(self.session.regex_query.cursor),
+            Field::TestString => None,
+        };
+        self.render.draw_regex_query(
+            &self.session.regex_query.string,
+            cursor,
+            diagnostic.is_some(),
+            &lit,
+            padding,
+            RE_ROW,
+        )?;
+        if let Some(diagnostic) = &diagnostic {
+            let error = self.render.theme().error;
+            self.render.draw_diagnostic(
+                &self.session.regex_query.string,
+                diagnostic,
+                error,
+                padding,
+                RE_ROW + 1,
+            )?;
+        } else if let Some(lint) = self.lints.get(self.lint) {
+            let accent = self.render.theme().accent;
+            let diagnostic = Diagnostic {
+                span: Some(lint.span.clone()),
+                message: lint.message.clone(),
+            };
+            self.render.draw_diagnostic(
+                &self.session.regex_query.string,
+                &diagnostic,
+                accent,
+                padding,
+                RE_ROW + 1,
+            )?;
+        }
+
+        for (input, skip, row) in [
+            (&self.session.regex_query, 0, RE_ROW),
+            (&self.session.test_string, self.hay_scroll, hay_row),
+        ] {
+            if let Some(selected) = input.selection() {
+                self.render
+                    .draw_selection(&input.string, selected, skip, padding, row)?;
+            }
+        }
+
+        if !self.lints.is_empty() {
+            self.draw_lints()?;
+        }
+
+        if !self.session.examples.is_empty() {
+            self.draw_examples()?;
+        }
+
+        if let Some(capture) = self.details {
+            self.draw_details(capture)?;
+        }
+
+        if self.debugger.is_some() {
+            self.draw_debugger()?;
+        }
+
+        if let Some(report) = &self.bench {
+            let row = self.render.bottom() + 2;
+            self.render.draw_panel("BENCHMARK", report, padding, row)?;
+        }
+
+        if let Some(lang) = self.code {
+            let row = self.render.bottom() + 2;
+            let code = self.generate_code(lang);
+            self.render.draw_panel(
+                &format!("CODE ({})", lang.name()),
+                code.lines(),
+                padding,
+                row,
+            )?;
+        }
+
+        if self.diagram.is_some() {
+            self.draw_diagram()?;
+        }
+
+        if self.debug {
+            self.draw_debug()?;
+        }
+
+        if self.overlay.is_some() {
+            self.draw_overlay()?;
+        }
+
+        Ok(())
     }
 
     fn handle_events(&mut self) -> io::Result<Change> {
+        if !event::poll(TICK)? {
+            let saved = self.autosave();
+            let traced = self.debugger.as_mut().is_some_and(Debugger::poll);
+            let suggested = self.poll_suggestions();
+            let benched = self.poll_bench();
+            return Ok(
+                if self.regex_cache.poll() || traced || suggested || benched || saved.content {
+                    Change::new().content().cursor()
+                } else {
+                    Change::new()
+                },
+            );
+        }
+        self.last_input = Instant::now();
+
         let change = match event::read()? {
             // it's important to check that the event is a key press event as
             // crossterm also emits key release and repeat events on Windows.
             Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
-                self.handle_key_event(key_event)
+                let shown = self.message.take().is_some();
+                let change = self.handle_key_event(key_event);
+                if shown { change.content() } else { change }
             }
+            Event::Paste(text) => self.paste(&text),
+            Event::Mouse(mouse_event) => return Ok(self.handle_mouse_event(mouse_event)),
             _ => Change::new(),
         };
+
+        // Edits invalidate the spans of the inspected capture, and typing should
+        // always keep the cursor in view
+        if change.content {
+            self.details = None;
+        }
+        if change.cursor {
+            self.scroll_to_cursor()?;
+        }
         Ok(change)
     }
 
+    fn handle_mouse_event(&mut self, mouse_event: MouseEvent) -> Change {
+        let MouseEvent {
+            kind, column, row, ..
+        } = mouse_event;
+        match kind {
+            MouseEventKind::Down(MouseButton::Left) => self.click(column, row),
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let at = self.field_offset(column);
+                self.current_field().select_to(at)
+            }
+            // The diagram is scrolled when the mouse is over it, and the test string
+            // anywhere else
+            MouseEventKind::ScrollDown | MouseEventKind::ScrollUp
+                if self.diagram.as_ref().is_some_and(|view| row > view.top) =>
+            {
+                let rows = if kind == MouseEventKind::ScrollDown {
+                    1
+                } else {
+                    -1
+                };
+                self.scroll_diagram(rows, 0)
+            }
+            MouseEventKind::ScrollRight | MouseEventKind::ScrollLeft
+                if self.diagram.as_ref().is_some_and(|view| row > view.top) =>
+            {
+                let cols = if kind == MouseEventKind::ScrollRight {
+                    1
+                } else {
+                    -1
+                };
+                self.scroll_diagram(0, cols * SCROLL_STEP as isize)
+            }
+            MouseEventKind::ScrollDown | MouseEventKind::ScrollRight => {
+                let len = self.session.test_string.string.chars().count();
+                self.hay_scroll = (self.hay_scroll + SCROLL_STEP).min(len.saturating_sub(1));
+                Change::new().content().cursor()
+            }
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollLeft => {
+                self.hay_scroll = self.hay_scroll.saturating_sub(SCROLL_STEP);
+                Change::new().content().cursor()
+            }
+            _ => Change::new(),
+        }
+    }
+
+    /// Places the cursor in the field that was clicked on, and opens the details of
+    /// the capture that was clicked on, if any.
+    fn click(&mut self, col: u16, row: u16) -> Change {
+        self.details = self.capture_at(col, row);
+        if row == RE_ROW {
+            self.field = Field::RegexQuery;
+        } else if row == self.layout.hay_row() {
+            self.field = Field::TestString;
+        } else {
+            return Change::new().content().cursor();
+        }
+        let at = self.field_offset(col);
+        self.current_field().place(at);
+        Change::new().content().cursor()
+    }
+
+    /// The character of the current field at the given column of the screen.
+    fn field_offset(&self, col: u16) -> usize {
+        let offset = col.saturating_sub(self.layout.left_padding()) as usize;
+        match self.field {
+            Field::RegexQuery => offset,
+            Field::TestString => offset + self.hay_scroll,
+        }
+    }
+
+    /// The capture drawn at the given position, either in the test string, where the
+    /// innermost one wins, or as an underline below it.
+    fn capture_at(&mut self, col: u16, row: u16) -> Option<Capture> {
+        let (padding, hay_row) = (self.layout.left_padding(), self.layout.hay_row());
+        if row < hay_row || col < padding {
+            return None;
+        }
+        let at = (col - padding) as usize + self.hay_scroll;
+        let pattern = self.session.pattern();
+        let hay = &self.session.test_string.string;
+        let Status::Ready(matches) = self.regex_cache.get_or_init(&pattern, hay) else {
+            return None;
+        };
+
+        for captures in matches {
+            let (_, infos) = render::layout(hay, captures);
+            let mut hits = infos
+                .iter()
+                .enumerate()
+                // Empty captures are still drawn one character wide
+                .filter(|(_, g)| g.start <= at && at < g.end.max(g.start + 1));
+            let hit = if row == hay_row {
+                hits.max_by_key(|(_, g)| g.layer)
+            } else {
+                hits.find(|(_, g)| row == hay_row + g.layer as u16 + 1)
+            };
+            if let Some((idx, _)) = hit {
+                return Some(captures[idx]);
+            }
+        }
+        None
+    }
+
+    /// Scrolls the test string just enough for its cursor to be visible.
+    fn scroll_to_cursor(&mut self) -> io::Result<()> {
+        let (cols, _) = terminal::size()?;
+        let width = cols.saturating_sub(self.layout.left_padding() + 1).max(1) as usize;
+        let cursor = self.session.test_string.cursor;
+        if cursor < self.hay_scroll {
+            self.hay_scroll = cursor;
+        } else if cursor >= self.hay_scroll + width {
+            self.hay_scroll = cursor + 1 - width;
+        }
+        Ok(())
+    }
+
     fn handle_key_event(&mut self, key_event: KeyEvent) -> Change {
+        if self.overlay.is_some() {
+            return self.handle_overlay_key(key_event);
+        }
+
+        match self.keymap.get(key_event) {
+            Some(action) => self.perform(action),
+            // Characters that aren't bound to anything are typed in
+            None => match key_event.code {
+                KeyCode::Char(ch)
+                    if !key_event
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                {
+                    self.current_field().insert(ch)
+                }
+                _ => Change::new(),
+            },
+        }
+    }
+
+    fn perform(&mut self, action: Action) -> Change {
+        match action {
+            Action::MoveLeft => self.current_field().move_cursor_left(),
+            Action::MoveRight => self.current_field().move_cursor_right(),
+            Action::MoveStart => self.current_field().move_cursor_start(),
+            Action::MoveEnd => self.current_field().move_cursor_end(),
+            Action::DeleteChar => self.current_field().delete_char(),
+            Action::SwitchField => self.switch(),
+            Action::ToggleFlag(ch) => self.toggle_flag(ch),
+            Action::ImportLiteral => self.import_literal(),
+            Action::CycleCode => self.cycle_code(),
+            Action::CopyCode => self.copy_code(),
+            Action::ToggleDebugger => self.toggle_debugger(),
+            Action::StepForward => self.step_debugger(Debugger::forward),
+            Action::StepBack => self.step_debugger(Debugger::back),
+            Action::ToggleBench => self.toggle_bench(),
+            Action::ToggleDebug => self.toggle_debug(),
+            Action::ToggleDiagram => self.toggle_diagram(),
+            Action::ScrollDiagramUp => self.scroll_diagram(-1, 0),
+            Action::ScrollDiagramDown => self.scroll_diagram(1, 0),
+            Action::ScrollDiagramLeft => self.scroll_diagram(0, -(SCROLL_STEP as isize)),
+            Action::ScrollDiagramRight => self.scroll_diagram(0, SCROLL_STEP as isize),
+            Action::SavePattern => self.open_overlay(Kind::SavePattern),
+            Action::Library => self.open_overlay(Kind::Library),
+            Action::History => self.open_history(),
+            Action::OpenSession => self.open_sessions(),
+            Action::ExportSession => self.export_session(),
+            Action::GenerateExamples => self.generate_examples(),
+            Action::ClearExamples => self.clear_examples(),
+            Action::AddMatchExample => self.add_example(true),
+            Action::AddNonMatchExample => self.add_example(false),
+            Action::Infer => self.infer(),
+            Action::NextLint => self.next_lint(),
+            Action::ApplyFix => self.apply_fix(),
+            Action::CommandPalette => self.open_overlay(Kind::Palette),
+            Action::Help => self.open_overlay(Kind::Help),
+            Action::Exit => self.exit(),
+        }
+    }
+
+    fn open_overlay(&mut self, kind: Kind) -> Change {
+        self.overlay = Some(Overlay::new(kind));
+        Change::new().content().cursor()
+    }
+
+    fn handle_overlay_key(&mut self, key_event: KeyEvent) -> Change {
+        let Some(overlay) = &mut self.overlay else {
+            return Change::new();
+        };
+        let results = overlay.results(
+            &self.library,
+            &self.history,
+            &self.samples,
+            &self.candidates,
+            &self.sessions,
+        );
+        let ctrl = key_event.modifiers.intersects(KeyModifiers::CONTROL);
         match key_event.code {
-            KeyCode::Char(ch) => {
-                if key_event.modifiers.intersects(KeyModifiers::CONTROL) {
-                    match ch {
-                        'h' => self.current_field().move_cursor_left(),
-                        'j' | 'k' | 'n' | 'p' => self.switch(),
-                        'l' => self.current_field().move_cursor_right(),
-                        _ => Change::new(),
+            KeyCode::Esc => {
+                self.overlay = None;
+                // The conflict is detected again on the next save
+                self.conflict = None;
+                self.exit_pending = false;
+            }
+            // The examples are added one by one, so the list stays open
+            KeyCode::Enter if overlay.kind == Kind::Examples => {
+                if let Some(Item::Sample(i)) = results.get(overlay.selected) {
+                    self.add_sample(*i);
+                }
+            }
+            KeyCode::Enter if overlay.kind == Kind::SavePattern => {
+                let form = overlay.query.string.clone();
+                self.save_pattern(&form);
+            }
+            KeyCode::Enter => {
+                let item = results.get(overlay.selected).copied();
+                self.overlay = None;
+                match item {
+                    Some(Item::Action(action)) => {
+                        self.perform(action);
                     }
-                } else {
-                    self.current_field().insert(ch)
+                    Some(Item::Reference(entry)) => {
+                        self.field = Field::RegexQuery;
+                        let (text, fill) = entry.insert();
+                        let query = &mut self.session.regex_query;
+                        let start = query.selection().map_or(query.cursor, |range| range.start);
+                        query.insert_str(&text);
+                        query.place(start + fill.start);
+                        if !fill.is_empty() {
+                            query.select_to(start + fill.end);
+                        }
+                    }
+                    Some(Item::Pattern(i)) => self.insert_pattern(i),
+                    Some(Item::Snapshot(i)) => self.restore(i),
+                    Some(Item::Resolution(resolution)) => self.resolve(resolution),
+                    Some(Item::Sample(i)) => self.add_sample(i),
+                    Some(Item::Candidate(i)) => self.load_candidate(i),
+                    Some(Item::Session(i)) => self.open_session(i),
+                    None => {}
                 }
             }
-            KeyCode::Backspace => self.current_field().delete_char(),
+            KeyCode::Up => overlay.select(-1, results.len()),
+            KeyCode::Down | KeyCode::Tab => overlay.select(1, results.len()),
+            KeyCode::PageUp => overlay.select(-(OVERLAY_ENTRIES as isize), results.len()),
+            KeyCode::PageDown => overlay.select(OVERLAY_ENTRIES as isize, results.len()),
+            KeyCode::Char('p') if ctrl => overlay.select(-1, results.len()),
+            KeyCode::Char('n') if ctrl => overlay.select(1, results.len()),
             KeyCode::Left => {
-                if key_event.modifiers.intersects(KeyModifiers::CONTROL) {
-                    self.current_field().move_cursor_start()
-                } else {
-                    self.current_field().move_cursor_left()
-                }
+                overlay.query.move_cursor_left();
             }
             KeyCode::Right => {
-                if key_event.modifiers.intersects(KeyModifiers::CONTROL) {
-                    self.current_field().move_cursor_end()
-                } else {
-                    self.current_field().move_cursor_right()
+                overlay.query.move_cursor_right();
+            }
+            KeyCode::Backspace => {
+                overlay.query.delete_char();
+                overlay.selected = 0;
+            }
+            KeyCode::Char(ch) if !ctrl => {
+                overlay.query.insert(ch);
+                overlay.selected = 0;
+            }
+            _ => return Change::new(),
+        }
+        Change::new().content().cursor()
+    }
+
+    fn save_pattern(&mut self, form: &str) {
+        // An empty form keeps the overlay open, since there is no name to save under
+        let Some(pattern) = Pattern::parse(form, &self.session.regex_query.string) else {
+            return;
+        };
+        self.overlay = None;
+
+        let name = pattern.name.clone();
+        let replaced = self.library.add(pattern);
+        self.message = Some(match self.library.save() {
+            Ok(()) if replaced => format!("replaced `{name}` in the library"),
+            Ok(()) => format!("saved `{name}` to the library"),
+            Err(err) => err.to_string(),
+        });
+    }
+
+    /// Inserts the library's pattern at index `i` at the regex cursor.
+    fn insert_pattern(&mut self, i: usize) {
+        let pattern = &self.library.patterns[i].pattern;
+        // An alternation would take the rest of the pattern in with it
+        let text = if !self.session.regex_query.string.is_empty() && pattern.contains('|') {
+            format!("(?:{pattern})")
+        } else {
+            pattern.clone()
+        };
+        self.field = Field::RegexQuery;
+        self.session.regex_query.insert_str(&text);
+    }
+
+    /// Saves the session if it changed, once the user is idle or it's been a while.
+    fn autosave(&mut self) -> Change {
+        if !self.autosave {
+            return Change::new();
+        }
+        if self.saved.matches(&self.session) {
+            // Pick up changes made elsewhere while there is nothing to save
+            if self.overlay.is_none() && self.last_check.elapsed() >= AUTOSAVE_IDLE {
+                self.last_check = Instant::now();
+                if self.check_external_change() || self.message.is_some() {
+                    return Change::new().content().cursor();
                 }
             }
-            KeyCode::Tab | KeyCode::Up | KeyCode::Down => self.switch(),
-            KeyCode::Esc => self.exit(),
-            _ => Change::new(),
+            return Change::new();
+        }
+        let idle = self.last_input.elapsed() >= AUTOSAVE_IDLE && self.last_save < self.last_input;
+        if idle || self.last_save.elapsed() >= AUTOSAVE_INTERVAL {
+            self.save()
+        } else {
+            Change::new()
+        }
+    }
+
+    fn save(&mut self) -> Change {
+        self.last_save = Instant::now();
+        if self.check_external_change() {
+            return Change::new().content().cursor();
+        }
+        self.session.workspace = Some(self.workspace());
+        match self.session.save() {
+            Ok(()) => {
+                self.saved = self.session.snapshot();
+                Change::new()
+            }
+            Err(err) => {
+                self.message = Some(format!("failed to save session: {err}"));
+                Change::new().content()
+            }
+        }
+    }
+
+    /// Checks whether the session file was changed by someone else, before it's
+    /// overwritten. If the session wasn't edited since it was saved, their changes are
+    /// loaded, and otherwise the user is asked what to do. Returns whether they're asked.
+    fn check_external_change(&mut self) -> bool {
+        if self.session.access != Access::ReadWrite || self.conflict.is_some() {
+            return self.conflict.is_some();
+        }
+        match self.session.external_change() {
+            Ok(None) => false,
+            Ok(Some(theirs)) if self.saved.matches(&self.session) => {
+                self.session.resolve(theirs, Resolution::TakeTheirs);
+                self.saved = self.session.snapshot();
+                self.message = Some("reloaded the session, which was changed elsewhere".to_owned());
+                false
+            }
+            Ok(Some(theirs)) => {
+                self.conflict = Some(theirs);
+                self.overlay = Some(Overlay::new(Kind::Conflict));
+                true
+            }
+            Err(err) => {
+                self.message = Some(err.to_string());
+                false
+            }
+        }
+    }
+
+    fn resolve(&mut self, resolution: Resolution) {
+        let Some(theirs) = self.conflict.take() else {
+            return;
+        };
+        let kept_mine = self.session.resolve(theirs, resolution);
+        match resolution {
+            Resolution::TakeTheirs => self.saved = self.session.snapshot(),
+            _ => {
+                self.save();
+            }
+        }
+        if kept_mine {
+            self.message =
+                Some("merged, keeping your version of the fields you both changed".to_owned());
+        }
+        if self.exit_pending {
+            self.exit = true;
+        }
+    }
+
+    fn open_history(&mut self) -> Change {
+        if let SessionName::Scratch = self.session.name {
+            self.message =
+                Some("scratch sessions aren't saved, so they have no history".to_owned());
+            return Change::new().content();
+        }
+        // Save first, so the current version can be restored too
+        if !self.saved.matches(&self.session) {
+            self.save();
+            if self.conflict.is_some() {
+                return Change::new().content().cursor();
+            }
+        }
+        self.history = self.session.history();
+        self.open_overlay(Kind::History)
+    }
+
+    /// Restores the version at index `i` of the session's history.
+    fn restore(&mut self, i: usize) {
+        let snapshot = self.history[i].clone();
+        self.session.restore(&snapshot);
+        self.message = Some(format!("restored the version from {}", snapshot.age()));
+    }
+
+    fn open_sessions(&mut self) -> Change {
+        self.sessions = self.session.saved_names();
+        if self.sessions.is_empty() {
+            self.message = Some("there are no saved sessions".to_owned());
+            return Change::new().content();
         }
+        self.open_overlay(Kind::Sessions)
+    }
+
+    /// Saves the session and opens the saved session at index `i` in its place.
+    fn open_session(&mut self, i: usize) {
+        let name = self.sessions[i].clone();
+        if matches!(&self.session.name, SessionName::Name(current) if *current == name) {
+            return;
+        }
+        // The session is saved before it's closed, as on exit
+        if self.check_external_change() {
+            return;
+        }
+        self.session.workspace = Some(self.workspace());
+        if let Err(err) = self.session.save() {
+            self.message = Some(format!("failed to save session: {err}"));
+            return;
+        }
+        let mut session = match self.session.open(name) {
+            Ok(session) => session,
+            Err(err) => {
+                self.message = Some(err.to_string());
+                return;
+            }
+        };
+        if let Err(err) = session.lock() {
+            self.message = Some(format!("failed to lock session: {err}"));
+            return;
+        }
+
+        // Sessions saved without their UI state keep the panels as they are
+        let workspace = session.workspace.unwrap_or(Workspace {
+            hay_scroll: 0,
+            selected: None,
+            ..self.workspace()
+        });
+        self.session = session;
+        self.saved = self.session.snapshot();
+        self.last_valid = None;
+        self.lint = 0;
+        self.load_workspace(workspace);
+        if let SessionName::Name(name) = &self.session.name {
+            self.message = Some(format!("opened `{name}`"));
+        }
+    }
+
+    fn export_session(&mut self) -> Change {
+        // There is nothing sensible to do if the terminal can't be written to
+        let _ = self.render.copy(&self.session.export());
+        self.message = Some("copied the session's bundle to the clipboard".to_owned());
+        Change::new().content()
     }
 
     fn exit(&mut self) -> Change {
+        // The session is saved on exit, so changes made elsewhere are checked for first
+        if self.check_external_change() {
+            self.exit_pending = true;
+            return Change::new().content().cursor();
+        }
         self.exit = true;
         Change::new()
     }
 
+    fn generate_examples(&mut self) -> Change {
+        let pattern = self.session.pattern();
+        let Some(re) = self.regex_cache.compiled(&pattern) else {
+            self.message = Some("examples are generated from a valid pattern".to_owned());
+            return Change::new().content();
+        };
+        self.samples = generate::samples(
+            &self.session.regex_query.string,
+            self.session.flags,
+            re,
+            SAMPLES,
+        );
+        self.open_overlay(Kind::Examples)
+    }
+
+    /// Adds the generated string at index `i` to the session's examples.
+    fn add_sample(&mut self, i: usize) {
+        let sample = &self.samples[i];
+        let example = Example {
+            text: sample.text.clone(),
+            should_match: sample.matches,
+        };
+        if self.session.examples.contains(&example) {
+            self.message = Some(format!("{:?} is already an example", example.text));
+        } else {
+            self.message = Some(format!("added {:?} to the examples", example.text));
+            self.session.examples.push(example);
+        }
+    }
+
+    /// Adds the test string to the examples, as one the pattern should or shouldn't
+    /// match.
+    fn add_example(&mut self, should_match: bool) -> Change {
+        let text = self.session.test_string.string.clone();
+        if let Some(example) = self.session.examples.iter_mut().find(|e| e.text == text) {
+            example.should_match = should_match;
+        } else {
+            self.session.examples.push(Example { text, should_match });
+        }
+        Change::new().content()
+    }
+
+    fn infer(&mut self) -> Change {
+        self.candidates = infer::candidates(&self.session.examples, self.session.flags, CANDIDATES);
+        if self.candidates.is_empty() {
+            self.message = Some("patterns are inferred from examples that should match".to_owned());
+            return Change::new().content();
+        }
+        self.open_overlay(Kind::Candidates)
+    }
+
+    fn load_candidate(&mut self, i: usize) {
+        let pattern = self.candidates[i].pattern.clone();
+        self.field = Field::RegexQuery;
+        self.session.regex_query.set(pattern);
+    }
+
+    fn clear_examples(&mut self) -> Change {
+        self.session.examples.clear();
+        Change::new().content()
+    }
+
+    fn next_lint(&mut self) -> Change {
+        if self.lints.is_empty() {
+            return Change::new();
+        }
+        self.lint = (self.lint + 1) % self.lints.len();
+        Change::new().content()
+    }
+
+    fn apply_fix(&mut self) -> Change {
+        let Some(fix) = self.lints.get(self.lint).and_then(|lint| lint.fix.as_ref()) else {
+            return Change::new();
+        };
+        self.session.flags = fix.flags;
+        let pattern = fix.pattern.clone();
+        self.message = Some(format!("fixed: {}", fix.description));
+        self.session.regex_query.set(pattern)
+    }
+
+    fn toggle_diagram(&mut self) -> Change {
+        self.diagram = match self.diagram {
+            Some(_) => None,
+            None => Some(View::default()),
+        };
+        Change::new().content().cursor()
+    }
+
+    fn scroll_diagram(&mut self, rows: isize, cols: isize) -> Change {
+        match &mut self.diagram {
+            Some(view) => {
+                view.scroll(rows, cols);
+                Change::new().content().cursor()
+            }
+            None => Change::new(),
+        }
+    }
+
+    fn toggle_debug(&mut self) -> Change {
+        self.debug = !self.debug;
+        Change::new().content().cursor()
+    }
+
+    fn toggle_debugger(&mut self) -> Change {
+        self.debugger = match self.debugger {
+            Some(_) => None,
+            None => Some(Debugger::default()),
+        };
+        Change::new().content().cursor()
+    }
+
+    fn step_debugger(&mut self, step: fn(&mut Debugger) -> bool) -> Change {
+        if self.debugger.as_mut().is_some_and(step) {
+            Change::new().content().cursor()
+        } else {
+            Change::new()
+        }
+    }
+
+    fn toggle_bench(&mut self) -> Change {
+        self.benching = None;
+        self.bench = match self.bench {
+            Some(_) => None,
+            // Only benchmark patterns that are known to compile in time
+            None if self.regex_cache.compiled(&self.session.pattern()).is_none() => {
+                Some(vec!["waiting for a valid pattern".to_owned()])
+            }
+            None => {
+                let (re, hay) = (
+                    self.session.pattern().into_owned(),
+                    self.session.test_string.string.clone(),
+                );
+                let mut slot = Slot::spawn(move || {
+                    Ok(Bench::run(&re, &hay, BENCH_ITERATIONS, BENCH_MAX_TIME)?)
+                });
+                // The benchmark bounds itself by `BENCH_MAX_TIME`
+                slot.wait(GRACE, Duration::MAX);
+                self.benching = Some(slot);
+                self.poll_bench();
+                Some(vec!["running…".to_owned()])
+            }
+        };
+        Change::new().content().cursor()
+    }
+
+    /// Shows the report of the benchmark once it's done, returning whether it was shown.
+    fn poll_bench(&mut self) -> bool {
+        let Some(slot) = &mut self.benching else {
+            return false;
+        };
+        slot.wait(Duration::ZERO, Duration::MAX);
+        let Some(Slot::Done(result)) = self.benching.take_if(|slot| !slot.running()) else {
+            return false;
+        };
+        self.bench = Some(match result {
+            Ok(bench) => {
+                let report = bench.report(self.session.bench.as_ref());
+                self.session.bench = Some(bench);
+                report
+            }
+            Err(err) => vec![err.to_string()],
+        });
+        true
+    }
+
+    /// Inserts the pasted text as is. Source code literals are only unescaped on
+    /// request, since a pattern can look like one, e.g. `"[^"]*"`.
+    fn paste(&mut self, text: &str) -> Change {
+        // Fields are single-line
+        let text: String = text
+            .chars()
+            .filter(|ch| !matches!(ch, '\n' | '\r'))
+            .collect();
+        self.current_field().insert_str(&text)
+    }
+
+    /// Reinterprets the regex field as a source code literal, e.g. one pasted from a
+    /// codebase.
+    fn import_literal(&mut self) -> Change {
+        match literal::import(&self.session.regex_query.string) {
+            Some(imported) => {
+                self.session.flags = imported.flags;
+                self.session.regex_query.set(imported.pattern)
+            }
+            None => Change::new(),
+        }
+    }
+
+    fn toggle_flag(&mut self, ch: char) -> Change {
+        if self.session.flags.toggle(ch) {
+            Change::new().content().cursor()
+        } else {
+            Change::new()
+        }
+    }
+
+    fn cycle_code(&mut self) -> Change {
+        self.code = match self.code {
+            None => Some(Language::ALL[0]),
+            Some(lang) if lang.next() == Language::ALL[0] => None,
+            Some(lang) => Some(lang.next()),
+        };
+        Change::new().content().cursor()
+    }
+
+    fn copy_code(&mut self) -> Change {
+        if let Some(lang) = self.code {
+            let code = self.generate_code(lang);
+            // There is nothing sensible to do if the terminal can't be written to
+            let _ = self.render.copy(&code);
+        }
+        Change::new()
+    }
+
+    fn generate_code(&self, lang: Language) -> String {
+        codegen::generate(
+            lang,
+            &self.session.regex_query.string,
+            self.session.flags,
+            &self.session.test_string.string,
+        )
+    }
+
     fn current_field(&mut self) -> &mut Input {
         match self.field {
             Field::RegexQuery => &mut self.session.regex_query,
@@ -204,26 +1260,485 @@ impl<W: io::Write> App<W> {
         Change::new().cursor()
     }
 
-    fn draw_hay(&mut self, col: u16, row: u16) -> io::Result<()> {
-        match self.regex_cache.get_or_init(
-            &self.session.regex_query.string,
-            &self.session.test_string.string,
-        ) {
-            Ok(matches) => {
+    /// Draws the test string, returning the error the pattern failed to compile with,
+    /// and the capture groups that matched the character under the cursor.
+    fn draw_hay(
+        &mut self,
+        emphasis: Emphasis,
+        col: u16,
+        row: u16,
+    ) -> io::Result<(Option<String>, Vec<usize>)> {
+        let pattern = self.session.pattern();
+        let hay = &self.session.test_string.string;
+        let status = match self.regex_cache.get_or_init(&pattern, hay) {
+            Status::Ready(matches) => {
+                self.last_valid = Some(pattern.into_owned());
+                self.render
+                    .draw_regex_hay(hay, matches, emphasis, col, row)?;
+
+                let lit = match self.field {
+                    Field::TestString => {
+                        let cursor = self.session.test_string.byte_index();
+                        matches
+                            .iter()
+                            .flatten()
+                            .filter(|c| c.group > 0 && c.start <= cursor && cursor < c.end)
+                            .map(|c| c.group)
+                            .collect()
+                    }
+                    Field::RegexQuery => Vec::new(),
+                };
+                return Ok((None, lit));
+            }
+            Status::Compiling => "compiling…".to_owned(),
+            Status::Matching => "matching…".to_owned(),
+            Status::TimedOut(budget) => format!("timed out after {budget:?}"),
+            Status::Failed(err) => {
+                let error = err.to_string();
+                // Keep the matches of the last valid pattern visible while this one is invalid
+                match &self.last_valid {
+                    Some(last_valid) => match self.regex_cache.matched(last_valid, hay) {
+                        Some(matches) => {
+                            self.render
+                                .draw_regex_hay(hay, matches, Emphasis::Dimmed, col, row)?
+                        }
+                        None => self.render.draw_scrolled(hay, col, row)?,
+                    },
+                    None => self.render.draw_scrolled(hay, col, row)?,
+                }
+                return Ok((Some(error), Vec::new()));
+            }
+        };
+
+        self.render.draw_status(hay, &status, col, row)?;
+        Ok((None, Vec::new()))
+    }
+
+    /// The innermost capture group around the regex cursor.
+    fn group_at_regex_cursor(&self) -> Option<usize> {
+        let cursor = self.session.regex_query.byte_index();
+        highlight::structure(&self.session.regex_query.string)
+            .groups
+            .iter()
+            .filter(|g| g.open <= cursor && g.close.is_none_or(|close| cursor <= close))
+            .filter_map(|g| g.index.map(|index| (g.depth, index)))
+            .max()
+            .map(|(_, index)| index)
+    }
+
+    fn draw_debugger(&mut self) -> io::Result<()> {
+        let row = self.render.bottom() + 2;
+        let limits = *self.regex_cache.limits();
+        let (Some(debugger), Some(re)) = (
+            &mut self.debugger,
+            self.regex_cache.compiled(&self.session.pattern()),
+        ) else {
+            return self.render.at(
+                self.render.theme().muted,
+                "DEBUGGER  waiting for a valid pattern",
+                0,
+                row,
+            );
+        };
+
+        debugger.update(re, &self.session.test_string.string, &limits);
+        match debugger.status() {
+            Some(DebuggerStatus::Tracing) => {
+                self.render
+                    .at(self.render.theme().muted, "DEBUGGER  tracing…", 0, row)
+            }
+            Some(DebuggerStatus::Ready(report)) => self.render.draw_debugger(
+                report,
+                debugger.step(),
+                &self.session.test_string.string,
+                self.layout.left_padding(),
+                row,
+            ),
+            Some(DebuggerStatus::Failed(err)) => {
+                self.render.at(Color::Reset, "DEBUGGER", 0, row)?;
                 self.render
-                    .draw_regex_hay(&self.session.test_string.string, matches, col, row)
+                    .draw_error(&err, self.layout.left_padding(), row + 1)
             }
-            Err(err) => self.render.draw_error(&err.to_string(), col, row),
+            None => Ok(()),
         }
     }
 
+    fn draw_examples(&mut self) -> io::Result<()> {
+        let row = self.render.bottom() + 2;
+        let re = self.regex_cache.compiled(&self.session.pattern());
+        let mut expected = 0;
+        let lines: Vec<_> = self
+            .session
+            .examples
+            .iter()
+            .map(|example| {
+                let mark = match re {
+                    Some(re) if re.is_match(&example.text) == example.should_match => {
+                        expected += 1;
+                        '✓'
+                    }
+                    Some(_) => '✗',
+                    None => '?',
+                };
+                let kind = if example.should_match {
+                    "should match"
+                } else {
+                    "shouldn't match"
+                };
+                format!("{mark} {kind:<16} {:?}", example.text)
+            })
+            .collect();
+        let title = format!(
+            "EXAMPLES ({expected} of {} as expected)",
+            self.session.examples.len()
+        );
+        self.render
+            .draw_panel(&title, lines, self.layout.left_padding(), row)
+    }
+
+    fn draw_lints(&mut self) -> io::Result<()> {
+        const SHOWN: usize = 5;
+
+        let row = self.render.bottom() + 2;
+        let fix = self.keys(Action::ApplyFix);
+        // Keep the selected lint in view when there are more than fit
+        let skip = self.lint.saturating_sub(SHOWN - 1);
+        let mut lines: Vec<_> = self
+            .lints
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .take(SHOWN)
+            .map(|(i, lint)| {
+                let marker = if i == self.lint { '>' } else { ' ' };
+                match &lint.fix {
+                    Some(f) if i == self.lint => {
+                        format!("{marker} {}  ({fix}: {})", lint.message, f.description)
+                    }
+                    _ => format!("{marker} {}", lint.message),
+                }
+            })
+            .collect();
+        let more = self.lints.len() - skip - lines.len();
+        if more > 0 {
+            lines.push(format!("  and {more} more"));
+        }
+        let title = format!("LINTS ({}: next)", self.keys(Action::NextLint));
+        self.render
+            .draw_panel(&title, lines, self.layout.left_padding(), row)
+    }
+
+    fn draw_diagram(&mut self) -> io::Result<()> {
+        let row = self.render.bottom() + 2;
+        let Some(lines) = railroad::diagram(&self.session.regex_query.string, self.session.flags)
+        else {
+            return self.render.at(
+                self.render.theme().muted,
+                "DIAGRAM  waiting for a valid pattern",
+                0,
+                row,
+            );
+        };
+        let Some(view) = &mut self.diagram else {
+            return Ok(());
+        };
+
+        // The diagram takes up the rest of the screen, above the debug line
+        let (cols, rows) = terminal::size()?;
+        let padding = self.layout.left_padding();
+        let height = (rows.saturating_sub(row + 2) as usize).max(DIAGRAM_MIN_ROWS);
+        let width = cols.saturating_sub(padding) as usize;
+        let diagram_width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        view.top = row;
+        view.row = view.row.min(lines.len().saturating_sub(height));
+        view.col = view.col.min(diagram_width.saturating_sub(width));
+
+        let title = if lines.len() > height || diagram_width > width {
+            format!(
+                "DIAGRAM  rows {}-{} of {}, columns {}-{} of {}",
+                view.row + 1,
+                (view.row + height).min(lines.len()),
+                lines.len(),
+                view.col + 1,
+                (view.col + width).min(diagram_width),
+                diagram_width,
+            )
+        } else {
+            "DIAGRAM".to_owned()
+        };
+        let visible: Vec<String> = lines
+            .iter()
+            .skip(view.row)
+            .take(height)
+            .map(|line| line.chars().skip(view.col).take(width).collect())
+            .collect();
+        self.render.draw_panel(&title, visible, padding, row)
+    }
+
+    fn draw_details(&mut self, capture: Capture) -> io::Result<()> {
+        let row = self.render.bottom() + 2;
+        let Capture { group, start, end } = capture;
+        let name = self
+            .regex_cache
+            .compiled(&self.session.pattern())
+            .and_then(|re| re.capture_names().nth(group).flatten())
+            .map_or(String::new(), |name| format!(" ({name})"));
+        let text = self
+            .session
+            .test_string
+            .string
+            .get(start..end)
+            .unwrap_or_default();
+        let lines = [
+            format!("group {group}{name}"),
+            format!("span  {start}..{end}"),
+            format!("text  {text:?}"),
+        ];
+        self.render
+            .draw_panel("CAPTURE", lines, self.layout.left_padding(), row)
+    }
+
+    fn draw_overlay(&mut self) -> io::Result<()> {
+        let Some(overlay) = &self.overlay else {
+            return Ok(());
+        };
+        let results = overlay.results(
+            &self.library,
+            &self.history,
+            &self.samples,
+            &self.candidates,
+            &self.sessions,
+        );
+        // Keep the selected item in view
+        let first = overlay.selected.saturating_sub(OVERLAY_ENTRIES - 1);
+        let entries: Vec<_> = results
+            .iter()
+            .skip(first)
+            .take(OVERLAY_ENTRIES)
+            .map(|item| match (overlay.kind, item) {
+                (Kind::Palette, Item::Action(action)) => format!(
+                    " {:<48} {:<28} {}",
+                    action.description(),
+                    action.name(),
+                    self.keys(*action),
+                ),
+                (_, Item::Action(action)) => {
+                    format!(" {:<20} {}", self.keys(*action), action.description())
+                }
+                (_, Item::Pattern(i)) => {
+                    let pattern = &self.library.patterns[*i];
+                    format!(
+                        " {:<16} {:<20} {}",
+                        pattern.name,
+                        pattern.tags.join(", "),
+                        pattern.description
+                    )
+                }
+                (_, Item::Snapshot(i)) => {
+                    let snapshot = &self.history[*i];
+                    format!(
+                        " {:<12} {:<40} {}",
+                        snapshot.age(),
+                        snapshot.pattern,
+                        snapshot.test_string
+                    )
+                }
+                (_, Item::Sample(i)) => {
+                    let sample = &self.samples[*i];
+                    let kind = if sample.matches { "match" } else { "near miss" };
+                    let added = self.session.examples.iter().any(|e| e.text == sample.text);
+                    let added = if added { "(added)" } else { "" };
+                    format!(" {kind:<10} {:<40} {added}", format!("{:?}", sample.text))
+                }
+                (_, Item::Candidate(i)) => {
+                    let candidate = &self.candidates[*i];
+                    format!(
+                        " {:<48} {} of {} examples as expected",
+                        candidate.pattern,
+                        candidate.correct,
+                        self.session.examples.len()
+                    )
+                }
+                (_, Item::Session(i)) => {
+                    let name = &self.sessions[*i];
+                    let open = matches!(&self.session.name, SessionName::Name(n) if n == name);
+                    format!(" {name:<40} {}", if open { "(open)" } else { "" })
+                }
+                (_, Item::Resolution(resolution)) => {
+                    format!(" {:<12} {}", resolution.name(), resolution.description())
+                }
+                (_, Item::Reference(entry)) => {
+                    format!(
+                        " {:<20} {:<12} {}",
+                        entry.token, entry.category, entry.description
+                    )
+                }
+            })
+            .collect();
+        let (entries, selected) = match overlay.kind {
+            Kind::SavePattern => (self.save_preview(&overlay.query.string), None),
+            Kind::History => {
+                let mut entries = entries;
+                if let Some(Item::Snapshot(i)) = results.get(overlay.selected) {
+                    entries.push(String::new());
+                    entries.extend(self.diff(&self.history[*i]));
+                }
+                (entries, Some(overlay.selected - first))
+            }
+            Kind::Conflict => {
+                let mut entries = entries;
+                if let Some(theirs) = &self.conflict {
+                    entries.push(String::new());
+                    entries.push(" their changes:".to_owned());
+                    entries.extend(self.diff(&theirs.snapshot()));
+                }
+                (entries, Some(overlay.selected - first))
+            }
+            _ => (entries, Some(overlay.selected - first)),
+        };
+
+        let (cols, _) = terminal::size()?;
+        self.render.draw_overlay(
+            overlay.title(),
+            &overlay.query.string,
+            &entries,
+            selected,
+            OVERLAY_ROW,
+            cols as usize,
+        )
+    }
+
+    /// How the current session differs from `snapshot`, with the parts that switching
+    /// to it would remove in `[-...-]` and the parts it would add in `{+...+}`.
+    fn diff(&self, snapshot: &Snapshot) -> Vec<String> {
+        let session = &self.session;
+        let flags = session.flags.to_string();
+        // The ones that shouldn't match are marked with a `!`
+        let examples = |examples: &[Example]| -> String {
+            examples
+                .iter()
+                .map(|e| format!("{}{:?}", if e.should_match { "" } else { "!" }, e.text))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let (current, old) = (examples(&session.examples), examples(&snapshot.examples));
+        [
+            ("pattern", &session.regex_query.string, &snapshot.pattern),
+            ("flags", &flags, &snapshot.flags),
+            (
+                "test string",
+                &session.test_string.string,
+                &snapshot.test_string,
+            ),
+            ("examples", &current, &old),
+        ]
+        .into_iter()
+        .map(|(label, current, old)| format!(" {:<12} {}", format!("{label}:"), diff(current, old)))
+        .collect()
+    }
+
+    /// What saving the pattern with `form` would add to the library.
+    fn save_preview(&self, form: &str) -> Vec<String> {
+        let regex = &self.session.regex_query.string;
+        let mut lines = vec![format!(" pattern:     {regex}")];
+        if let Some(pattern) = Pattern::parse(form, regex) {
+            lines.push(format!(" name:        {}", pattern.name));
+            lines.push(format!(" tags:        {}", pattern.tags.join(", ")));
+            lines.push(format!(" description: {}", pattern.description));
+            if self.library.patterns.iter().any(|p| p.name == pattern.name) {
+                lines.push(" (replaces the pattern with the same name)".to_owned());
+            }
+        }
+        lines
+    }
+
+    /// The keys bound to `action`, as shown to the user.
+    fn keys(&self, action: Action) -> String {
+        let keys: Vec<_> = self
+            .keymap
+            .keys(action)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        if keys.is_empty() {
+            "(unbound)".to_owned()
+        } else {
+            keys.join(", ")
+        }
+    }
+
+    fn draw_debug(&mut self) -> io::Result<()> {
+        let stats = self.regex_cache.stats();
+        let capacity = self.regex_cache.capacity();
+        let text = format!(
+            "cache: regexes {}/{} hits, captures {}/{} hits, {} evicted | regexes {}/{}, haystacks {} (max {} each) | ~{} KiB",
+            stats.regex_hits,
+            stats.regex_hits + stats.regex_misses,
+            stats.captures_hits,
+            stats.captures_hits + stats.captures_misses,
+            stats.evictions,
+            stats.regexes,
+            capacity.regexes,
+            stats.haystacks,
+            capacity.haystacks,
+            stats.memory.div_ceil(1024),
+        );
+        let (_, rows) = terminal::size()?;
+        self.render
+            .at(self.render.theme().muted, text, 0, rows.saturating_sub(1))
+    }
+
     fn pos(&self) -> (u16, u16) {
+        if let Some(overlay) = &self.overlay {
+            let col = overlay.title().len() + 1 + overlay.query.cursor;
+            return (col as u16, OVERLAY_ROW);
+        }
+
+        let padding = self.layout.left_padding();
         match self.field {
-            Field::RegexQuery => (LEFT_PADDING + self.session.regex_query.cursor as u16, 0),
+            Field::RegexQuery => (padding + self.session.regex_query.cursor as u16, RE_ROW),
             Field::TestString => (
-                LEFT_PADDING + self.session.test_string.cursor as u16,
-                LINES_BETWEEN,
+                padding
+                    + self
+                        .session
+                        .test_string
+                        .cursor
+                        .saturating_sub(self.hay_scroll) as u16,
+                self.layout.hay_row(),
             ),
         }
     }
 }
+
+/// A character diff of `old` against `new`, which keeps their common prefix and suffix
+/// and marks the differing part between them.
+fn diff(old: &str, new: &str) -> String {
+    if old == new {
+        return format!("{old} (unchanged)").trim_start().to_owned();
+    }
+    let (old_chars, new_chars): (Vec<_>, Vec<_>) = (old.chars().collect(), new.chars().collect());
+    let prefix = old_chars
+        .iter()
+        .zip(&new_chars)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old_chars[prefix..]
+        .iter()
+        .rev()
+        .zip(new_chars[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let part = |chars: &[char]| chars.iter().collect::<String>();
+    let removed = part(&old_chars[prefix..old_chars.len() - suffix]);
+    let added = part(&new_chars[prefix..new_chars.len() - suffix]);
+    let mut diff = part(&old_chars[..prefix]);
+    if !removed.is_empty() {
+        diff.push_str(&format!("[-{removed}-]"));
+    }
+    if !added.is_empty() {
+        diff.push_str(&format!("{{+{added}+}}"));
+    }
+    diff.push_str(&part(&old_chars[old_chars.len() - suffix..]));
+    diff
+}
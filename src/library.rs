@@ -0,0 +1,248 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::persist;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to read pattern library `{0}`: {1}")]
+    Read(PathBuf, io::Error),
+    #[error("invalid pattern library `{0}`: {1}")]
+    Parse(PathBuf, toml::de::Error),
+    #[error("failed to write pattern library `{0}`: {1}")]
+    Write(PathBuf, io::Error),
+    #[error(
+        "invalid pattern library `{0}`: pattern `{1}` spans several lines, which the pattern field can't hold"
+    )]
+    MultiLine(PathBuf, String),
+}
+
+/// A reusable, named pattern.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pattern {
+    pub name: String,
+    pub pattern: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl Pattern {
+    /// Parses the `<name> [#tag ...] [description]` form the pattern is saved with from
+    /// the UI. Words starting with `#` are tags, and the rest of them are the description.
+    pub fn parse(form: &str, pattern: &str) -> Option<Self> {
+        let mut words = form.split_whitespace();
+        let name = words.next()?.to_owned();
+        let (tags, description): (Vec<_>, Vec<_>) =
+            words.partition(|word| word.len() > 1 && word.starts_with('#'));
+        Some(Self {
+            name,
+            pattern: pattern.to_owned(),
+            tags: tags.iter().map(|tag| tag[1..].to_owned()).collect(),
+            description: description.join(" "),
+        })
+    }
+}
+
+/// The library file, as written.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct File {
+    #[serde(rename = "pattern")]
+    patterns: Vec<Pattern>,
+}
+
+/// The patterns saved to the library file, which starts out with a few common ones.
+pub struct Library {
+    pub patterns: Vec<Pattern>,
+    path: PathBuf,
+}
+
+impl Library {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let patterns = match read(path) {
+            Ok(patterns) => patterns,
+            Err(Error::Read(_, err)) if err.kind() == io::ErrorKind::NotFound => starter(),
+            Err(err) => return Err(err),
+        };
+        Ok(Self {
+            patterns,
+            path: path.to_owned(),
+        })
+    }
+
+    /// Adds `pattern`, replacing the pattern with the same name if there is one.
+    /// Returns whether a pattern was replaced.
+    pub fn add(&mut self, pattern: Pattern) -> bool {
+        match self.patterns.iter_mut().find(|p| p.name == pattern.name) {
+            Some(existing) => {
+                *existing = pattern;
+                true
+            }
+            None => {
+                self.patterns.push(pattern);
+                false
+            }
+        }
+    }
+
+    /// Adds the patterns of the library file at `path`. Returns how many were added,
+    /// and how many of them replaced patterns with the same name.
+    pub fn import(&mut self, path: &Path) -> Result<(usize, usize), Error> {
+        let patterns = read(path)?;
+        let count = patterns.len();
+        let replaced = patterns
+            .into_iter()
+            .filter(|pattern| self.add(pattern.clone()))
+            .count();
+        Ok((count, replaced))
+    }
+
+    /// The library in the format of the library file.
+    pub fn export(&self) -> String {
+        let file = File {
+            patterns: self.patterns.clone(),
+        };
+        // This unwrap is okay, because the file consists of nothing but strings
+        toml::to_string(&file).unwrap()
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let write = || {
+            if let Some(p) = self.path.parent() {
+                fs::create_dir_all(p)?;
+            }
+            persist::write_atomic(&self.path, &self.export(), true)
+        };
+        write().map_err(|err| Error::Write(self.path.clone(), err))
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<Pattern>, Error> {
+    let s = fs::read_to_string(path).map_err(|err| Error::Read(path.to_owned(), err))?;
+    let file: File = toml::from_str(&s).map_err(|err| Error::Parse(path.to_owned(), err))?;
+    // Patterns are inserted into the single-line pattern field, and saved with the session
+    if let Some(pattern) = file
+        .patterns
+        .iter()
+        .find(|p| p.pattern.contains(['\n', '\r']))
+    {
+        return Err(Error::MultiLine(path.to_owned(), pattern.name.clone()));
+    }
+    Ok(file.patterns)
+}
+
+const HEX: &str = "[0-9A-Fa-f]";
+const OCTET: &str = r"(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)";
+const DATE: &str = r"\d{4}-(?:0[1-9]|1[0-2])-(?:0[1-9]|[12]\d|3[01])";
+const TIME: &str = r"(?:[01]\d|2[0-3]):[0-5]\d:[0-5]\d";
+
+/// The patterns the library starts out with.
+fn starter() -> Vec<Pattern> {
+    let ipv4 = format!(r"\b(?:{OCTET}\.){{3}}{OCTET}\b");
+    // The forms with the longer tails come first, since the leftmost alternative
+    // that matches wins, even if a later one would match more
+    let h = format!("{HEX}{{1,4}}");
+    let ipv6 = [
+        format!("(?:{h}:){{7}}{h}"),
+        format!("{h}:(?::{h}){{1,6}}"),
+        format!("(?:{h}:){{1,2}}(?::{h}){{1,5}}"),
+        format!("(?:{h}:){{1,3}}(?::{h}){{1,4}}"),
+        format!("(?:{h}:){{1,4}}(?::{h}){{1,3}}"),
+        format!("(?:{h}:){{1,5}}(?::{h}){{1,2}}"),
+        format!("(?:{h}:){{1,6}}:{h}"),
+        format!("(?:{h}:){{1,7}}:"),
+        format!("::(?:{h}(?::{h}){{0,6}})?"),
+    ]
+    .join("|");
+    let uuid = format!(r"\b{HEX}{{8}}-{HEX}{{4}}-{HEX}{{4}}-{HEX}{{4}}-{HEX}{{12}}\b");
+    let identifier = r"(?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*)";
+    let semver = format!(
+        r"\b(?P<major>0|[1-9]\d*)\.(?P<minor>0|[1-9]\d*)\.(?P<patch>0|[1-9]\d*)(?:-(?P<prerelease>{identifier}(?:\.{identifier})*))?(?:\+(?P<build>[0-9a-zA-Z-]+(?:\.[0-9a-zA-Z-]+)*))?"
+    );
+
+    vec![
+        starter_pattern(
+            "ipv4",
+            ipv4,
+            &["network"],
+            "IPv4 address in dotted decimal, e.g. 192.168.0.1",
+        ),
+        starter_pattern(
+            "ipv6",
+            ipv6,
+            &["network"],
+            "IPv6 address, including the `::` compressed forms",
+        ),
+        starter_pattern(
+            "iso-date",
+            format!(r"\b{DATE}\b"),
+            &["date", "iso-8601"],
+            "ISO 8601 calendar date, e.g. 2024-02-29",
+        ),
+        starter_pattern(
+            "iso-datetime",
+            format!(r"\b{DATE}T{TIME}(?:\.\d+)?(?:Z|[+-](?:[01]\d|2[0-3]):[0-5]\d)?"),
+            &["date", "iso-8601"],
+            "ISO 8601 date and time, with optional fractions and offset",
+        ),
+        starter_pattern(
+            "uuid",
+            uuid,
+            &["id"],
+            "UUID in its hyphenated form, of any version",
+        ),
+        starter_pattern(
+            "semver",
+            semver,
+            &["version"],
+            "Semantic version, with groups for each of its parts",
+        ),
+        starter_pattern(
+            "email",
+            r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b".to_owned(),
+            &["web"],
+            "Email address, the practical subset rather than all of RFC 5322",
+        ),
+    ]
+}
+
+fn starter_pattern(name: &str, pattern: String, tags: &[&str], description: &str) -> Pattern {
+    Pattern {
+        name: name.to_owned(),
+        pattern,
+        tags: tags.iter().map(ToString::to_string).collect(),
+        description: description.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multi_line_patterns_are_rejected() {
+        let dir = std::env::temp_dir().join(format!("replay-library-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (good, bad) = (dir.join("good.toml"), dir.join("bad.toml"));
+        fs::write(&good, "[[pattern]]\nname = \"digits\"\npattern = '\\d+'\n").unwrap();
+        fs::write(&bad, "[[pattern]]\nname = \"lines\"\npattern = \"a\\nb\"\n").unwrap();
+
+        let mut library = Library::load(&good).unwrap();
+        assert_eq!(library.patterns[0].pattern, r"\d+");
+        assert!(matches!(
+            library.import(&bad),
+            Err(Error::MultiLine(_, name)) if name == "lines"
+        ));
+        assert_eq!(library.patterns.len(), 1);
+        assert!(matches!(Library::load(&bad), Err(Error::MultiLine(..))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,391 @@
+use std::ops::{Range, RangeInclusive};
+
+use regex::Regex;
+use regex_syntax::ast::{
+    self, Ast, ClassPerlKind, ClassSet, ClassSetItem, GroupKind, LiteralKind, RepetitionKind,
+    RepetitionOp,
+};
+
+use crate::flags::Flags;
+
+/// A likely mistake in the pattern, which parses fine but probably doesn't do what it
+/// was meant to.
+pub struct Lint {
+    /// Byte range in the pattern the lint points at.
+    pub span: Range<usize>,
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+/// A quick fix for a lint, as the pattern and flags it results in.
+pub struct Fix {
+    pub description: String,
+    pub pattern: String,
+    pub flags: Flags,
+}
+
+/// Lints `re`, using the matches of `regex`, its compiled form, in `hay` for the lints
+/// that depend on what the pattern is used for.
+pub fn lint(re: &str, flags: Flags, regex: Option<&Regex>, hay: &str) -> Vec<Lint> {
+    let Ok(ast) = ast::parse::ParserBuilder::new()
+        .ignore_whitespace(flags.ignore_whitespace)
+        .build()
+        .parse(re)
+    else {
+        return Vec::new();
+    };
+
+    let mut linter = Linter {
+        re,
+        flags,
+        lints: Vec::new(),
+    };
+    linter.visit(&ast);
+    if let Some(regex) = regex {
+        linter.unanchored(&ast, regex, hay);
+    }
+    linter
+        .lints
+        .sort_by_key(|lint| (lint.span.start, lint.span.end));
+    linter.lints
+}
+
+struct Linter<'a> {
+    re: &'a str,
+    flags: Flags,
+    lints: Vec<Lint>,
+}
+
+impl Linter<'_> {
+    fn visit(&mut self, ast: &Ast) {
+        match ast {
+            Ast::Concat(concat) => {
+                for window in concat.asts.windows(3) {
+                    if let [prev, Ast::Dot(span), next] = window
+                        && is_word_like(prev)
+                        && is_word_like(next)
+                    {
+                        self.unescaped_dot(range(span));
+                    }
+                }
+                concat.asts.iter().for_each(|ast| self.visit(ast));
+            }
+            Ast::Alternation(alternation) => {
+                for (i, ast) in alternation.asts.iter().enumerate() {
+                    if let Ast::Empty(span) = ast {
+                        self.empty_alternative(alternation, range(span), i);
+                    }
+                }
+                alternation.asts.iter().for_each(|ast| self.visit(ast));
+            }
+            Ast::Repetition(repetition) => {
+                self.nested_repetition(repetition);
+                self.visit(&repetition.ast);
+            }
+            Ast::Group(group) => self.visit(&group.ast),
+            Ast::ClassBracketed(class) => self.class(&class.kind),
+            _ => {}
+        }
+    }
+
+    fn unescaped_dot(&mut self, span: Range<usize>) {
+        self.lints.push(Lint {
+            message: "`.` matches any character, escape it to match a literal dot".to_owned(),
+            fix: Some(self.replace(span.clone(), r"\.", "escape the dot")),
+            span,
+        });
+    }
+
+    /// The `i`th alternative of `alternation`, spanning `span`, is empty.
+    fn empty_alternative(&mut self, alternation: &ast::Alternation, span: Range<usize>, i: usize) {
+        let (first, last) = (i == 0, i + 1 == alternation.asts.len());
+        // The `|` that separates the empty alternative from its neighbour, which is
+        // pointed at. With `x`, whitespace may separate them instead.
+        let bar = if first {
+            span.start..span.start + 1
+        } else {
+            span.start.saturating_sub(1)..span.start
+        };
+        let span = if self.re.get(bar.clone()) == Some("|") {
+            bar
+        } else {
+            span
+        };
+
+        // Removing the empty alternative would stop the alternation from matching
+        // nothing, so the others are made optional instead: greedily if it comes last,
+        // and lazily if it comes first, since the first alternative that matches wins.
+        // One in the middle is only tried when the ones before it fail, which a
+        // quantifier can't express.
+        let empty = alternation
+            .asts
+            .iter()
+            .filter(|ast| matches!(ast, Ast::Empty(_)))
+            .count();
+        let fix = (empty == 1 && (first || last)).then(|| {
+            let rest =
+                &alternation.asts[usize::from(first)..alternation.asts.len() - usize::from(last)];
+            let text =
+                &self.re[rest[0].span().start.offset..rest[rest.len() - 1].span().end.offset];
+            let quantifier = if first { "??" } else { "?" };
+            let text = match rest {
+                [ast] if is_atom(ast) => format!("{text}{quantifier}"),
+                _ => format!("(?:{text}){quantifier}"),
+            };
+            self.replace(
+                range(&alternation.span),
+                &text,
+                "make the other alternatives optional",
+            )
+        });
+        self.lints.push(Lint {
+            message: "empty alternative, which makes the alternation match nothing too".to_owned(),
+            fix,
+            span,
+        });
+    }
+
+    /// `a**` or `(?:a+)*`, which can be written with a single quantifier.
+    fn nested_repetition(&mut self, outer: &ast::Repetition) {
+        let inner = match &*outer.ast {
+            Ast::Repetition(inner) => inner,
+            Ast::Group(group) => match (&group.kind, &*group.ast) {
+                (GroupKind::NonCapturing(flags), Ast::Repetition(inner))
+                    if flags.items.is_empty() =>
+                {
+                    inner
+                }
+                _ => return,
+            },
+            _ => return,
+        };
+
+        let span = range(&outer.span);
+        let fix = combine(&outer.op, &inner.op)
+            .filter(|_| outer.greedy == inner.greedy)
+            .map(|op| {
+                let lazy = if outer.greedy { "" } else { "?" };
+                let text = format!("{}{op}{lazy}", &self.re[range(inner.ast.span())]);
+                self.replace(span.clone(), &text, &format!("use a single `{op}`"))
+            });
+        self.lints.push(Lint {
+            span,
+            message: "redundant nested quantifier".to_owned(),
+            fix,
+        });
+    }
+
+    fn class(&mut self, set: &ClassSet) {
+        let ClassSet::Item(item) = set else {
+            return;
+        };
+        let items = match item {
+            ClassSetItem::Union(union) => &union.items[..],
+            item => std::slice::from_ref(item),
+        };
+
+        let mut seen: Vec<RangeInclusive<char>> = Vec::new();
+        for item in items {
+            let (chars, span) = match item {
+                ClassSetItem::Literal(literal) => (literal.c..=literal.c, &literal.span),
+                ClassSetItem::Range(class_range) => {
+                    self.mixed_case_range(class_range);
+                    (class_range.start.c..=class_range.end.c, &class_range.span)
+                }
+                ClassSetItem::Bracketed(class) => {
+                    self.class(&class.kind);
+                    continue;
+                }
+                _ => continue,
+            };
+            if seen
+                .iter()
+                .any(|s| s.contains(chars.start()) && s.contains(chars.end()))
+            {
+                let span = range(span);
+                let item = &self.re[span.clone()];
+                self.lints.push(Lint {
+                    message: format!("`{item}` is already in the class"),
+                    fix: Some(self.replace(span.clone(), "", &format!("remove `{item}`"))),
+                    span,
+                });
+            }
+            seen.push(chars);
+        }
+    }
+
+    /// `[A-z]`, which also matches the punctuation between `Z` and `a`.
+    fn mixed_case_range(&mut self, class_range: &ast::ClassSetRange) {
+        let (start, end) = (class_range.start.c, class_range.end.c);
+        if !(start.is_ascii_uppercase() && end.is_ascii_lowercase()) {
+            return;
+        }
+        let span = range(&class_range.span);
+        let text = format!("{start}-Za-{end}");
+        self.lints.push(Lint {
+            message: format!("`{start}-{end}` also matches the punctuation between `Z` and `a`"),
+            fix: Some(self.replace(span.clone(), &text, &format!("use `{text}`"))),
+            span,
+        });
+    }
+
+    /// An unanchored pattern that matches part of a longer word, which is a common
+    /// mistake in patterns meant to validate their whole input.
+    fn unanchored(&mut self, ast: &Ast, regex: &Regex, hay: &str) {
+        if has_assertion(ast) {
+            return;
+        }
+        let is_word = |ch: Option<char>| ch.is_some_and(char::is_alphanumeric);
+        let Some(m) = regex.find_iter(hay).find(|m| {
+            !m.is_empty()
+                && ((is_word(hay[..m.start()].chars().next_back())
+                    && is_word(m.as_str().chars().next()))
+                    || (is_word(hay[m.end()..].chars().next())
+                        && is_word(m.as_str().chars().next_back())))
+        }) else {
+            return;
+        };
+
+        let text = if matches!(ast, Ast::Alternation(_)) {
+            format!("^(?:{})$", self.re)
+        } else {
+            format!("^{}$", self.re)
+        };
+        self.lints.push(Lint {
+            span: 0..self.re.len(),
+            message: format!(
+                "matches `{}` inside a longer word, anchor it if it validates the whole input",
+                m.as_str()
+            ),
+            fix: Some(Fix {
+                description: "anchor it with `^` and `$`".to_owned(),
+                pattern: text,
+                flags: self.flags,
+            }),
+        });
+    }
+
+    fn replace(&self, span: Range<usize>, text: &str, description: &str) -> Fix {
+        let mut pattern = self.re.to_owned();
+        pattern.replace_range(span, text);
+        Fix {
+            description: description.to_owned(),
+            pattern,
+            flags: self.flags,
+        }
+    }
+}
+
+pub(crate) fn range(span: &ast::Span) -> Range<usize> {
+    span.start.offset..span.end.offset
+}
+
+/// Whether `ast` matches parts of words or numbers, such as the `example` and `com` of
+/// `example.com`, or the `\d+` of `\d+.\d+`.
+fn is_word_like(ast: &Ast) -> bool {
+    match ast {
+        Ast::Literal(literal) => {
+            literal.kind == LiteralKind::Verbatim && literal.c.is_alphanumeric()
+        }
+        Ast::ClassPerl(class) => {
+            !class.negated && matches!(class.kind, ClassPerlKind::Digit | ClassPerlKind::Word)
+        }
+        Ast::ClassBracketed(class) => !class.negated,
+        Ast::Repetition(repetition) => is_word_like(&repetition.ast),
+        _ => false,
+    }
+}
+
+/// Whether a quantifier after `ast` applies to all of it.
+fn is_atom(ast: &Ast) -> bool {
+    matches!(
+        ast,
+        Ast::Literal(_)
+            | Ast::Dot(_)
+            | Ast::ClassUnicode(_)
+            | Ast::ClassPerl(_)
+            | Ast::ClassBracketed(_)
+            | Ast::Group(_)
+    )
+}
+
+fn has_assertion(ast: &Ast) -> bool {
+    match ast {
+        Ast::Assertion(_) => true,
+        Ast::Repetition(repetition) => has_assertion(&repetition.ast),
+        Ast::Group(group) => has_assertion(&group.ast),
+        Ast::Alternation(alternation) => alternation.asts.iter().any(has_assertion),
+        Ast::Concat(concat) => concat.asts.iter().any(has_assertion),
+        _ => false,
+    }
+}
+
+/// The single quantifier that repeats like `inner` nested in `outer`, for the simple
+/// quantifiers.
+fn combine(outer: &RepetitionOp, inner: &RepetitionOp) -> Option<char> {
+    use RepetitionKind::*;
+    match (&outer.kind, &inner.kind) {
+        (ZeroOrOne, ZeroOrOne) => Some('?'),
+        (OneOrMore, OneOrMore) => Some('+'),
+        (ZeroOrOne | ZeroOrMore | OneOrMore, ZeroOrOne | ZeroOrMore | OneOrMore) => Some('*'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(re: &str) -> Range<usize> {
+        let lints = lint(re, Flags::default(), None, "");
+        assert_eq!(lints.len(), 1, "{re}");
+        lints[0].span.clone()
+    }
+
+    #[test]
+    fn empty_alternatives_point_at_their_bar() {
+        assert_eq!(span("a||b"), 1..2);
+        assert_eq!(span("(|a)"), 1..2);
+        assert_eq!(span("a|"), 1..2);
+        assert_eq!(span("|a"), 0..1);
+    }
+
+    /// The spans of all the captures of `re` in `hay`.
+    fn captures(re: &str, hay: &str) -> Vec<Option<Range<usize>>> {
+        Regex::new(re)
+            .unwrap()
+            .captures_iter(hay)
+            .flat_map(|c| c.iter().map(|m| m.map(|m| m.range())).collect::<Vec<_>>())
+            .collect()
+    }
+
+    #[test]
+    fn empty_alternatives_become_optional() {
+        for (re, fixed) in [
+            ("a|", "a?"),
+            ("|a", "a??"),
+            ("(|a)", "(a??)"),
+            ("(ab|)c", "((?:ab)?)c"),
+            ("x(?:a|bc|)", "x(?:(?:a|bc)?)"),
+            ("(|a)b", "(a??)b"),
+        ] {
+            let lints = lint(re, Flags::default(), None, "");
+            let pattern = &lints[0].fix.as_ref().unwrap().pattern;
+            assert_eq!(pattern, fixed);
+            for hay in ["", "a", "ab abc", "xbc xa x", "aab"] {
+                assert_eq!(captures(re, hay), captures(pattern, hay), "{re} on {hay:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn empty_alternatives_in_the_middle_are_not_fixed() {
+        for re in ["a||b", "(a||b)c"] {
+            let lints = lint(re, Flags::default(), None, "");
+            assert!(lints[0].fix.is_none(), "{re}");
+        }
+        // Fixing one of several would leave the others
+        let lints = lint("||a", Flags::default(), None, "");
+        assert!(lints.iter().all(|lint| lint.fix.is_none()));
+    }
+}
@@ -0,0 +1,237 @@
+use crate::flags::Flags;
+
+/// A pattern recovered from a string literal.
+pub struct Imported {
+    pub pattern: String,
+    pub flags: Flags,
+}
+
+/// Detects a pattern written as a source code literal and unescapes it.
+///
+/// Supported are Rust raw strings (`r"..."`, `r#"..."#`), Python strings with their
+/// prefixes (`r'...'`, `b"..."`), regular Rust, Python, JavaScript and JSON strings,
+/// and JavaScript regex literals (`/.../flags`). Returns `None` if `s` isn't a literal.
+pub fn import(s: &str) -> Option<Imported> {
+    let s = s.trim();
+    let (pattern, mut flags) = if let Some(pattern) = rust_raw(s) {
+        (pattern.to_owned(), Flags::default())
+    } else if let Some(imported) = js_regex(s) {
+        imported
+    } else {
+        (python_or_quoted(s)?, Flags::default())
+    };
+
+    // Move leading inline flags, e.g. `(?i)`, into the flag set
+    let mut extracted = flags;
+    let pattern = match pattern
+        .strip_prefix("(?")
+        .and_then(|rest| rest.split_once(')'))
+    {
+        Some((inline, rest))
+            if !inline.is_empty() && inline.chars().all(|ch| extracted.set(ch)) =>
+        {
+            flags = extracted;
+            rest.to_owned()
+        }
+        _ => pattern,
+    };
+
+    // Fields are single-line, so line breaks are kept as the equivalent escapes
+    let pattern = pattern.replace('\n', "\\n").replace('\r', "\\r");
+
+    Some(Imported { pattern, flags })
+}
+
+/// `r"..."` or `r#"..."#`, with any amount of `#`s.
+fn rust_raw(s: &str) -> Option<&str> {
+    let rest = s.strip_prefix('r')?;
+    let hashes = rest.len() - rest.trim_start_matches('#').len();
+    let rest = rest[hashes..].strip_prefix('"')?;
+    rest.strip_suffix(&"#".repeat(hashes))?.strip_suffix('"')
+}
+
+/// `/pattern/flags`
+fn js_regex(s: &str) -> Option<(String, Flags)> {
+    let rest = s.strip_prefix('/')?;
+    let (pattern, letters) = rest.rsplit_once('/')?;
+    // The pattern of a regex literal can't contain an unescaped `/`
+    let mut escaped = false;
+    for ch in pattern.chars() {
+        if ch == '/' && !escaped {
+            return None;
+        }
+        escaped = ch == '\\' && !escaped;
+    }
+    if pattern.is_empty() || escaped {
+        return None;
+    }
+
+    let mut flags = Flags::default();
+    for ch in letters.chars() {
+        match ch {
+            'i' | 'm' | 's' => {
+                flags.set(ch);
+            }
+            // Global, sticky, indices and Unicode modes don't affect what matches here
+            'g' | 'y' | 'd' | 'u' | 'v' => {}
+            _ => return None,
+        }
+    }
+
+    Some((pattern.replace("\\/", "/"), flags))
+}
+
+/// A quoted string, optionally with Python's string prefixes.
+fn python_or_quoted(s: &str) -> Option<String> {
+    let prefix = s.len() - s.trim_start_matches(['r', 'R', 'b', 'B', 'u', 'U']).len();
+    if prefix > 2 {
+        return None;
+    }
+    let raw = s[..prefix].contains(['r', 'R']);
+    let rest = &s[prefix..];
+
+    let quote = rest.chars().next().filter(|ch| matches!(ch, '"' | '\''))?;
+    let content = ["'''", "\"\"\""]
+        .into_iter()
+        .find_map(|triple| rest.strip_prefix(triple)?.strip_suffix(triple))
+        .or_else(|| rest.strip_prefix(quote)?.strip_suffix(quote))?;
+
+    if raw {
+        Some(content.to_owned())
+    } else {
+        unescape(content)
+    }
+}
+
+/// Unescapes the escape sequences shared by Rust, Python, JavaScript and JSON strings.
+///
+/// Unknown escapes are kept as is, the way Python treats them, since they are most
+/// likely regex escapes such as `\d`.
+fn unescape(s: &str) -> Option<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+
+        match chars.next()? {
+            '\\' => out.push('\\'),
+            '"' => out.push('"'),
+            '\'' => out.push('\''),
+            '/' => out.push('/'),
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            't' => out.push('\t'),
+            '0' => out.push('\0'),
+            'a' => out.push('\x07'),
+            'b' => out.push('\x08'),
+            'f' => out.push('\x0c'),
+            'v' => out.push('\x0b'),
+            'x' => out.push(hex(&mut chars, 2)?),
+            'u' => {
+                let rest = chars.as_str();
+                if let Some(braced) = rest.strip_prefix('{') {
+                    // Rust and JavaScript's `\u{...}`
+                    let (digits, after) = braced.split_once('}')?;
+                    out.push(char::from_u32(u32::from_str_radix(digits, 16).ok()?)?);
+                    chars = after.chars();
+                } else {
+                    let unit = hex_value(&mut chars, 4)?;
+                    if (0xd800..0xdc00).contains(&unit) {
+                        // A UTF-16 surrogate pair, as written by JavaScript and JSON
+                        if chars.next()? != '\\' || chars.next()? != 'u' {
+                            return None;
+                        }
+                        let low = hex_value(&mut chars, 4)?;
+                        out.push(char::decode_utf16([unit as u16, low as u16]).next()?.ok()?);
+                    } else {
+                        out.push(char::from_u32(unit)?);
+                    }
+                }
+            }
+            'U' => out.push(hex(&mut chars, 8)?),
+            other => {
+                out.push('\\');
+                out.push(other);
+            }
+        }
+    }
+    Some(out)
+}
+
+fn hex_value(chars: &mut std::str::Chars, digits: usize) -> Option<u32> {
+    let s: String = chars.by_ref().take(digits).collect();
+    if s.len() != digits {
+        return None;
+    }
+    u32::from_str_radix(&s, 16).ok()
+}
+
+fn hex(chars: &mut std::str::Chars, digits: usize) -> Option<char> {
+    char::from_u32(hex_value(chars, digits)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(s: &str) -> Option<String> {
+        import(s).map(|imported| imported.pattern)
+    }
+
+    #[test]
+    fn rust_strings() {
+        assert_eq!(pattern(r#""\\d+\\.\\d+""#).unwrap(), r"\d+\.\d+");
+        assert_eq!(pattern(r#"r"\d+""#).unwrap(), r"\d+");
+        assert_eq!(pattern(r##"r#"a"b"#"##).unwrap(), r#"a"b"#);
+        assert_eq!(pattern(r#""\u{e9}\x41""#).unwrap(), "éA");
+    }
+
+    #[test]
+    fn python_strings() {
+        assert_eq!(pattern(r"r'\d+'").unwrap(), r"\d+");
+        assert_eq!(pattern(r"'\\d+'").unwrap(), r"\d+");
+        assert_eq!(pattern(r#"b"\\d+""#).unwrap(), r"\d+");
+        assert_eq!(pattern(r#"Rb'\w'"#).unwrap(), r"\w");
+        assert_eq!(pattern(r#"'''it's'''"#).unwrap(), "it's");
+        // Unknown escapes are regex escapes, which Python keeps
+        assert_eq!(pattern(r"'\d\.'").unwrap(), r"\d\.");
+    }
+
+    #[test]
+    fn javascript_and_json_strings() {
+        assert_eq!(pattern(r#""\\d+\u00e9""#).unwrap(), r"\d+é");
+        assert_eq!(pattern(r#""\ud83d\ude00""#).unwrap(), "😀");
+        assert_eq!(pattern(r#""a\/b\"c""#).unwrap(), r#"a/b"c"#);
+        // Line breaks are kept as escapes, since fields are single-line
+        assert_eq!(pattern(r#""a\nb""#).unwrap(), r"a\nb");
+        assert!(import(r#""\ud83d""#).is_none());
+    }
+
+    #[test]
+    fn javascript_regex_literals() {
+        let imported = import(r"/\d+\/\d+/gi").unwrap();
+        assert_eq!(imported.pattern, r"\d+/\d+");
+        assert_eq!(imported.flags.to_string(), "i");
+        assert!(import("/a/q").is_none());
+        assert!(import("/a/b/").is_none());
+    }
+
+    #[test]
+    fn inline_flags() {
+        let imported = import(r#""(?ms)^a$""#).unwrap();
+        assert_eq!(imported.pattern, "^a$");
+        assert_eq!(imported.flags.to_string(), "ms");
+        // Scoped flags stay in the pattern
+        assert_eq!(pattern(r#""(?i:a)b""#).unwrap(), "(?i:a)b");
+    }
+
+    #[test]
+    fn not_literals() {
+        assert!(import(r"\d+").is_none());
+        assert!(import(r#""unterminated"#).is_none());
+        assert!(import(r#""trailing\""#).is_none());
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+/// Hashes a key, so caches don't have to keep an owned copy of every pattern and haystack.
+pub fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A least-recently-used map keyed by hashes, holding at most `capacity` entries.
+pub struct Lru<V> {
+    map: HashMap<u64, (V, u64)>,
+    capacity: usize,
+    tick: u64,
+    evictions: u64,
+}
+
+impl<V> Lru<V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            map: HashMap::new(),
+            capacity: capacity.max(1),
+            tick: 0,
+            evictions: 0,
+        }
+    }
+
+    pub fn contains(&self, key: u64) -> bool {
+        self.map.contains_key(&key)
+    }
+
+    /// Returns the entry and marks it as the most recently used.
+    pub fn get_mut(&mut self, key: u64) -> Option<&mut V> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.map.get_mut(&key).map(|(value, used)| {
+            *used = tick;
+            value
+        })
+    }
+
+    /// Inserts an entry, evicting the least recently used one if the map is full.
+    pub fn insert(&mut self, key: u64, value: V) -> &mut V {
+        if !self.map.contains_key(&key)
+            && self.map.len() >= self.capacity
+            && let Some(oldest) = self
+                .map
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| *key)
+        {
+            self.map.remove(&oldest);
+            self.evictions += 1;
+        }
+
+        self.tick += 1;
+        self.map.insert(key, (value, self.tick));
+        // This `unwrap` is okay, because the entry was inserted right above
+        &mut self.map.get_mut(&key).unwrap().0
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.map.values().map(|(value, _)| value)
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.map.values_mut().map(|(value, _)| value)
+    }
+}
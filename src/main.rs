@@ -1,19 +1,214 @@
-use std::{env, io};
+use std::{env, fs, io, time::Duration};
 
-use anyhow::Context;
+use anyhow::{Context, bail};
 use crossterm::terminal;
-use replay::{App, persist::Session};
+use replay::{
+    App,
+    bench::Bench,
+    codegen::{self, Language},
+    config::Config,
+    library::Library,
+    persist::{Access, Resolution, Session, SessionName},
+};
+
+const BENCH_ITERATIONS: u32 = 1000;
 
 fn main() -> anyhow::Result<()> {
-    let session = if let Some(name) = env::args().nth(1) {
-        Session::fetch(name)?
-    } else {
-        Session::scratch()
+    let config = Config::load()?;
+    let mut args: Vec<_> = env::args().skip(1).collect();
+    // Accepted anywhere, e.g. `replay --read-only MY_SESSION`
+    let read_only = match args.iter().position(|arg| arg == "--read-only") {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    };
+    let mut args = args.into_iter();
+    let mut session = match args.next() {
+        Some(command) if read_only && is_command(&command) => {
+            bail!("`--read-only` only applies to opening a session")
+        }
+        Some(command) if command == "bench" => return bench(args, &config),
+        Some(command) if command == "code" => return code(args, &config),
+        Some(command) if command == "library" => return library(args, &config),
+        Some(command) if command == "export" => return export(args, &config),
+        Some(command) if command == "import" => return import(args, &config),
+        // Opens sessions named after a command, e.g. `replay open bench`
+        Some(command) if command == "open" => {
+            let name = args
+                .next()
+                .with_context(|| "usage: replay open <session> [--read-only]")?;
+            Session::fetch(name, &config)?
+        }
+        Some(name) => Session::fetch(name, &config)?,
+        None => Session::scratch(&config),
     };
+    if let Some(arg) = args.next() {
+        bail!("unknown argument: `{arg}`");
+    }
+    if read_only {
+        session.access = Access::ReadOnly;
+    }
+    session.lock().with_context(|| "failed to lock session")?;
+
+    let library = Library::load(&config.library)?;
 
     terminal::enable_raw_mode()?;
-    let session = App::new(&mut io::stdout(), session).run();
+    let session = App::new(&mut io::stdout(), session, library, config).run();
     terminal::disable_raw_mode()?;
 
     session?.save().with_context(|| "failed to save session")
 }
+
+fn is_command(arg: &str) -> bool {
+    ["bench", "code", "library", "export", "import", "open"].contains(&arg)
+}
+
+/// Takes the session's lock before it's saved from the command line, failing if it's
+/// open in replay, whose changes would be overwritten.
+fn lock(session: &mut Session) -> anyhow::Result<()> {
+    session.lock().with_context(|| "failed to lock session")?;
+    if session.access == Access::Locked {
+        bail!("the session is open in another instance of replay, close it first");
+    }
+    Ok(())
+}
+
+/// `replay bench <session> [--file <path>] [--iterations <n>]`
+fn bench(mut args: impl Iterator<Item = String>, config: &Config) -> anyhow::Result<()> {
+    let name = args
+        .next()
+        .with_context(|| "usage: replay bench <session> [--file <path>] [--iterations <n>]")?;
+    let mut session = Session::fetch(name, config)?;
+    lock(&mut session)?;
+
+    let mut hay = None;
+    let mut iterations = BENCH_ITERATIONS;
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .with_context(|| format!("missing value for `{arg}`"))?;
+        match arg.as_str() {
+            "--file" => {
+                hay = Some(
+                    fs::read_to_string(&value)
+                        .with_context(|| format!("failed to read `{value}`"))?,
+                )
+            }
+            "--iterations" => {
+                iterations = value
+                    .parse()
+                    .with_context(|| format!("invalid iterations count: `{value}`"))?
+            }
+            _ => bail!("unknown argument: `{arg}`"),
+        }
+    }
+
+    let hay = hay.as_deref().unwrap_or(&session.test_string.string);
+    let result = Bench::run(&session.pattern(), hay, iterations, Duration::MAX)?;
+    for line in result.report(session.bench.as_ref()) {
+        println!("{line}");
+    }
+
+    // Pick up edits made by hand while the benchmark ran
+    if let Some(theirs) = session.external_change()? {
+        session.resolve(theirs, Resolution::TakeTheirs);
+    }
+    session.bench = Some(result);
+    session.save().with_context(|| "failed to save session")
+}
+
+/// `replay code <session> [language]`
+fn code(mut args: impl Iterator<Item = String>, config: &Config) -> anyhow::Result<()> {
+    let languages = Language::ALL.map(|lang| lang.name()).join("|");
+    let name = args
+        .next()
+        .with_context(|| format!("usage: replay code <session> [{languages}]"))?;
+    let lang = match args.next() {
+        Some(lang) => Language::from_name(&lang)
+            .with_context(|| format!("unknown language `{lang}`, expected one of: {languages}"))?,
+        None => Language::Rust,
+    };
+
+    let session = Session::fetch(name, config)?;
+    print!(
+        "{}",
+        codegen::generate(
+            lang,
+            &session.regex_query.string,
+            session.flags,
+            &session.test_string.string,
+        )
+    );
+    Ok(())
+}
+
+/// `replay export <session>`
+fn export(mut args: impl Iterator<Item = String>, config: &Config) -> anyhow::Result<()> {
+    let name = args
+        .next()
+        .with_context(|| "usage: replay export <session>")?;
+    if !Session::exists(&name, config) {
+        bail!("no session named `{name}`");
+    }
+    print!("{}", Session::fetch(name, config)?.export());
+    Ok(())
+}
+
+/// `replay import <file> [--as <name>] [--force]`
+fn import(mut args: impl Iterator<Item = String>, config: &Config) -> anyhow::Result<()> {
+    let file = args
+        .next()
+        .with_context(|| "usage: replay import <file> [--as <name>] [--force]")?;
+    let mut name = None;
+    let mut force = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--as" => name = Some(args.next().with_context(|| "missing value for `--as`")?),
+            "--force" => force = true,
+            _ => bail!("unknown argument: `{arg}`"),
+        }
+    }
+
+    let bundle = fs::read_to_string(&file).with_context(|| format!("failed to read `{file}`"))?;
+    let mut session = Session::import(&bundle, name, force, config)?;
+    lock(&mut session)?;
+    session.save().with_context(|| "failed to save session")?;
+    if let SessionName::Name(name) = &session.name {
+        println!("imported session `{name}`");
+    }
+    Ok(())
+}
+
+/// `replay library [list | import <file> | export [file]]`
+fn library(mut args: impl Iterator<Item = String>, config: &Config) -> anyhow::Result<()> {
+    const USAGE: &str = "usage: replay library [list | import <file> | export [file]]";
+    let mut library = Library::load(&config.library)?;
+    match args.next().as_deref() {
+        None | Some("list") => {
+            for pattern in &library.patterns {
+                println!("{}  [{}]", pattern.name, pattern.tags.join(", "));
+                if !pattern.description.is_empty() {
+                    println!("    {}", pattern.description);
+                }
+                println!("    {}", pattern.pattern);
+            }
+        }
+        Some("import") => {
+            let file = args.next().with_context(|| USAGE)?;
+            let (count, replaced) = library.import(file.as_ref())?;
+            library.save()?;
+            println!("imported {count} patterns ({replaced} replaced existing ones)");
+        }
+        Some("export") => match args.next() {
+            Some(file) => {
+                fs::write(&file, library.export())
+                    .with_context(|| format!("failed to write `{file}`"))?;
+            }
+            None => print!("{}", library.export()),
+        },
+        Some(arg) => bail!("unknown argument: `{arg}`\n{USAGE}"),
+    }
+    Ok(())
+}
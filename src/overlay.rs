@@ -0,0 +1,170 @@
+use crate::{
+    fuzzy,
+    generate::Sample,
+    infer::Candidate,
+    input::Input,
+    keymap::Action,
+    library::Library,
+    persist::{Resolution, Snapshot},
+    reference::{Entry, REFERENCE},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// The command palette, which runs actions.
+    Palette,
+    /// The key bindings, followed by the regex quick reference.
+    Help,
+    /// The pattern library, whose patterns are inserted into the pattern.
+    Library,
+    /// The form the pattern is saved to the library with, which has no results.
+    SavePattern,
+    /// The session's history, whose versions are restored.
+    History,
+    /// The ways to reconcile the session with changes made to it elsewhere.
+    Conflict,
+    /// Strings generated from the pattern, which are added to the session's examples.
+    Examples,
+    /// Patterns inferred from the session's examples, which replace the pattern.
+    Candidates,
+    /// The saved sessions, which replace the open one.
+    Sessions,
+}
+
+#[derive(Clone, Copy)]
+pub enum Item {
+    Action(Action),
+    Reference(&'static Entry),
+    /// The index of a pattern in the library.
+    Pattern(usize),
+    /// The index of a version in the session's history.
+    Snapshot(usize),
+    Resolution(Resolution),
+    /// The index of a generated string.
+    Sample(usize),
+    /// The index of an inferred pattern.
+    Candidate(usize),
+    /// The index of a saved session's name.
+    Session(usize),
+}
+
+/// A searchable list drawn over the screen, whose items are filtered by a fuzzy
+/// search as the query is typed.
+pub struct Overlay {
+    pub kind: Kind,
+    pub query: Input,
+    /// The index of the selected item among the results.
+    pub selected: usize,
+}
+
+impl Overlay {
+    pub fn new(kind: Kind) -> Self {
+        Self {
+            kind,
+            query: Input::default(),
+            selected: 0,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self.kind {
+            Kind::Palette => "COMMAND:",
+            // Rust's regex crate is the only flavour, so it's the reference shown
+            Kind::Help => "HELP (Rust regex):",
+            Kind::Library => "LIBRARY:",
+            Kind::SavePattern => "SAVE AS (name #tag description):",
+            Kind::History => "HISTORY:",
+            Kind::Conflict => "SESSION CHANGED ELSEWHERE:",
+            Kind::Examples => "GENERATED EXAMPLES:",
+            Kind::Candidates => "INFERRED PATTERNS:",
+            Kind::Sessions => "OPEN SESSION:",
+        }
+    }
+
+    /// The items matching the query, best first.
+    pub fn results(
+        &self,
+        library: &Library,
+        history: &[Snapshot],
+        samples: &[Sample],
+        candidates: &[Candidate],
+        sessions: &[String],
+    ) -> Vec<Item> {
+        let query = &self.query.string;
+        let score = |texts: &[&str]| texts.iter().filter_map(|t| fuzzy::score(query, t)).max();
+
+        let actions = Action::ALL
+            .into_iter()
+            .filter(|action| self.kind == Kind::Help || *action != Action::CommandPalette)
+            .map(|action| {
+                (
+                    score(&[action.description(), action.name()]),
+                    Item::Action(action),
+                )
+            });
+        let mut results: Vec<_> = match self.kind {
+            Kind::Palette => actions.collect(),
+            Kind::Help => actions
+                .chain(REFERENCE.iter().map(|entry| {
+                    let texts = [entry.token, entry.description, entry.category];
+                    (score(&texts), Item::Reference(entry))
+                }))
+                .collect(),
+            Kind::Library => library
+                .patterns
+                .iter()
+                .enumerate()
+                .map(|(i, pattern)| {
+                    let mut texts = vec![pattern.name.as_str(), &pattern.description];
+                    texts.extend(pattern.tags.iter().map(String::as_str));
+                    (score(&texts), Item::Pattern(i))
+                })
+                .collect(),
+            Kind::SavePattern => Vec::new(),
+            Kind::Conflict => Resolution::ALL
+                .into_iter()
+                .map(|resolution| {
+                    let texts = [resolution.name(), resolution.description()];
+                    (score(&texts), Item::Resolution(resolution))
+                })
+                .collect(),
+            // The newest versions first
+            Kind::History => (0..history.len())
+                .rev()
+                .map(|i| {
+                    let snapshot = &history[i];
+                    let texts = [snapshot.pattern.as_str(), &snapshot.test_string];
+                    (score(&texts), Item::Snapshot(i))
+                })
+                .collect(),
+            Kind::Examples => samples
+                .iter()
+                .enumerate()
+                .map(|(i, sample)| (score(&[&sample.text]), Item::Sample(i)))
+                .collect(),
+            Kind::Candidates => candidates
+                .iter()
+                .enumerate()
+                .map(|(i, candidate)| (score(&[&candidate.pattern]), Item::Candidate(i)))
+                .collect(),
+            Kind::Sessions => sessions
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (score(&[name]), Item::Session(i)))
+                .collect(),
+        };
+        results.retain(|(score, _)| score.is_some());
+
+        // The sort is stable, so equally good matches keep their usual order
+        results.sort_by(|(a, _), (b, _)| b.cmp(a));
+        results.into_iter().map(|(_, item)| item).collect()
+    }
+
+    /// Moves the selection by `delta`, staying within `len` results.
+    pub fn select(&mut self, delta: isize, len: usize) {
+        self.selected = self
+            .selected
+            .saturating_add_signed(delta)
+            .min(len.saturating_sub(1));
+    }
+}
@@ -1,14 +1,22 @@
 use std::{
+    borrow::Cow,
     fmt::Display,
-    fs, io,
-    path::{Path, PathBuf},
+    fs,
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime},
 };
 
-use anyhow::Context;
+use serde::{Deserialize, Serialize};
 
-use crate::input::Input;
+use crate::{bench::Bench, codegen::Language, config::Config, flags::Flags, input::Input};
 
-const INVALID_CHARS: [char; 3] = [' ', '/', '\\'];
+/// The version of the bundle format written by [`Session::export`].
+const BUNDLE_VERSION: u32 = 2;
+/// Rust's regex crate, the only supported flavour.
+const FLAVOUR: &str = "rust";
+/// How many versions of a session its history keeps.
+const HISTORY_LIMIT: usize = 50;
 
 pub enum SessionName {
     Scratch,
@@ -31,125 +39,1055 @@ impl Display for SessionName {
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("session name contains invalid char: `{0}`")]
+    #[error("session name contains invalid char: {0:?}")]
     InvalidName(char),
+    #[error("session name must not be empty")]
+    EmptyName,
+    #[error("session name `{0}` differs only in case from the existing session `{1}`")]
+    Collision(String, String),
     #[error("session file contains invalid format: {0}")]
     InvalidFormat(FormatError),
+    #[error("failed to read session file: {0}")]
+    Read(io::Error),
+    #[error("invalid session bundle: {0}")]
+    InvalidBundle(toml::de::Error),
+    #[error("session bundle version {0} is newer than this version of replay supports")]
+    BundleVersion(u32),
+    #[error("unsupported regex flavour `{0}`, only `{FLAVOUR}` is supported")]
+    Flavour(String),
+    #[error(
+        "session `{0}` already exists and differs from the bundle, import it with `--as <name>` or `--force`"
+    )]
+    Conflict(String),
+    #[error("the bundle's {0} spans several lines, which sessions can't hold")]
+    MultiLine(&'static str),
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum FormatError {
-    #[error("session file must include exactly 2 lines")]
+    #[error("session file must include at least 2 lines")]
     Lines,
     #[error("the cursor position and content must be separated with a `:`")]
     Separator,
     #[error("cursor position must be a string representation of a `usize`")]
     Cursor,
+    #[error("invalid `{0}` entry")]
+    Entry(String),
+}
+
+/// Whether changes to a session are saved.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    ReadWrite,
+    /// Opened read-only on request.
+    ReadOnly,
+    /// Opened read-only, since another instance of replay has the session open.
+    Locked,
+}
+
+/// How to reconcile a session with changes made to its file by someone else.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Take their changes to the fields that weren't changed here, keeping ours where
+    /// both changed the same field.
+    Merge,
+    /// Overwrite their changes.
+    KeepMine,
+    /// Drop our changes.
+    TakeTheirs,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 3] = [Self::Merge, Self::KeepMine, Self::TakeTheirs];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Merge => "merge",
+            Self::KeepMine => "keep mine",
+            Self::TakeTheirs => "take theirs",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Merge => "take their changes, keeping yours where you both changed a field",
+            Self::KeepMine => "overwrite their changes with yours",
+            Self::TakeTheirs => "drop your changes and load theirs",
+        }
+    }
+}
+
+/// The state of the UI, restored when the session is opened again.
+#[derive(Clone, Copy, Default)]
+pub struct Workspace {
+    /// Whether the test string has the cursor, rather than the pattern.
+    pub hay_focused: bool,
+    /// How many characters the test string is scrolled by.
+    pub hay_scroll: usize,
+    pub debugger: bool,
+    pub bench: bool,
+    pub code: Option<Language>,
+    pub diagram: bool,
+    pub debug: bool,
+    /// The capture whose details are shown, as its group and its span in bytes.
+    pub selected: Option<(usize, usize, usize)>,
+}
+
+/// A string kept with the session, along with whether the pattern should match it.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Example {
+    pub text: String,
+    pub should_match: bool,
 }
 
 pub struct Session {
     pub name: SessionName,
     pub regex_query: Input,
     pub test_string: Input,
+    pub flags: Flags,
+    pub examples: Vec<Example>,
+    /// The last benchmark run on this session.
+    pub bench: Option<Bench>,
+    /// The state of the UI, if it was saved with the session.
+    pub workspace: Option<Workspace>,
+    pub access: Access,
+    /// The directory the session is saved in.
+    dir: PathBuf,
+    /// The contents of the session file when it was last read or written, to tell
+    /// whether it has been changed by someone else since.
+    disk: Option<String>,
+    /// The advisory lock on the session, held while it's open for writing.
+    lock: Option<fs::File>,
 }
 
 impl Session {
-    pub fn fetch(name: String) -> Result<Self, Error> {
+    pub fn fetch(name: String, config: &Config) -> Result<Self, Error> {
         validate_name(&name)?;
 
-        let path = get_path(&name);
+        let path = find_path(&config.sessions_dir, &name);
+        if let Some(existing) = find_collision(&config.sessions_dir, &name) {
+            return Err(Error::Collision(name, existing));
+        }
+
+        let mut session = Self {
+            name: SessionName::Name(name),
+            ..Self::scratch(config)
+        };
+        parse_session(&path, &mut session)?;
+
+        Ok(session)
+    }
 
-        let (regex_query, test_string) = parse_session(&path)?;
+    /// Opens the saved session named `name` from the same directory as this one. It's
+    /// read-only if this one was opened read-only.
+    pub fn open(&self, name: String) -> Result<Self, Error> {
+        validate_name(&name)?;
 
-        Ok(Self {
+        let path = find_path(&self.dir, &name);
+        let mut session = Self {
             name: SessionName::Name(name),
-            regex_query,
-            test_string,
-        })
+            access: match self.access {
+                Access::Locked => Access::ReadWrite,
+                access => access,
+            },
+            ..self.blank()
+        };
+        parse_session(&path, &mut session)?;
+
+        Ok(session)
+    }
+
+    /// The names of the sessions saved in the same directory as this one, sorted.
+    pub fn saved_names(&self) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            // Backups, histories and locks have extensions, so they don't decode
+            .filter_map(|entry| decode_name(entry.ok()?.file_name().to_str()?))
+            .collect();
+        names.sort();
+        names
     }
 
-    pub fn scratch() -> Self {
+    /// Whether a session named `name` has been saved.
+    pub fn exists(name: &str, config: &Config) -> bool {
+        validate_name(name).is_ok() && find_path(&config.sessions_dir, name).exists()
+    }
+
+    /// A session that isn't saved, starting out with the configured defaults.
+    pub fn scratch(config: &Config) -> Self {
         Self {
             name: SessionName::Scratch,
             regex_query: Input::default(),
             test_string: Input::default(),
+            flags: config.flags,
+            examples: Vec::new(),
+            bench: None,
+            workspace: None,
+            access: Access::ReadWrite,
+            dir: config.sessions_dir.clone(),
+            disk: None,
+            lock: None,
+        }
+    }
+
+    /// Takes the session's advisory lock, which makes the session read-only if another
+    /// instance of replay holds it. Only instances of replay respect the lock.
+    pub fn lock(&mut self) -> io::Result<()> {
+        let SessionName::Name(ref name) = self.name else {
+            return Ok(());
+        };
+        if self.access != Access::ReadWrite {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.dir.join(format!("{}.lock", encode_name(name))))?;
+        match file.try_lock() {
+            Ok(()) => self.lock = Some(file),
+            Err(fs::TryLockError::WouldBlock) => self.access = Access::Locked,
+            Err(fs::TryLockError::Error(err)) => return Err(err),
+        }
+        Ok(())
+    }
+
+    /// The session as it's now saved, if its file was changed since the session last
+    /// read or wrote it.
+    pub fn external_change(&self) -> Result<Option<Session>, Error> {
+        let SessionName::Name(ref name) = self.name else {
+            return Ok(None);
+        };
+        let disk = match fs::read_to_string(find_path(&self.dir, name)) {
+            Ok(s) => Some(s),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(Error::Read(err)),
+        };
+        if disk == self.disk {
+            return Ok(None);
+        }
+
+        let mut theirs = self.blank();
+        if let Some(s) = &disk {
+            parse_content(s, &mut theirs)?;
+        }
+        theirs.disk = disk;
+        Ok(Some(theirs))
+    }
+
+    /// Reconciles the session with `theirs`, the session as changed by someone else.
+    /// Returns whether a merge had to keep our version of a field both changed.
+    pub fn resolve(&mut self, theirs: Session, resolution: Resolution) -> bool {
+        let mut conflict = false;
+        match resolution {
+            Resolution::Merge => {
+                let mut base = self.blank();
+                if let Some(s) = &self.disk {
+                    // The file was parsed when it was read, so it's valid
+                    let _ = parse_content(s, &mut base);
+                }
+                let mut merge = |ours: &mut Input, theirs: Input, base: &Input| {
+                    if ours.string == base.string {
+                        *ours = theirs;
+                    } else {
+                        conflict |= theirs.string != base.string && theirs.string != ours.string;
+                    }
+                };
+                merge(&mut self.regex_query, theirs.regex_query, &base.regex_query);
+                merge(&mut self.test_string, theirs.test_string, &base.test_string);
+                if self.flags == base.flags {
+                    self.flags = theirs.flags;
+                } else {
+                    conflict |= theirs.flags != base.flags && theirs.flags != self.flags;
+                }
+                if self.examples == base.examples {
+                    self.examples = theirs.examples;
+                } else {
+                    conflict |=
+                        theirs.examples != base.examples && theirs.examples != self.examples;
+                }
+                self.bench = self.bench.or(theirs.bench);
+            }
+            Resolution::KeepMine => {}
+            Resolution::TakeTheirs => {
+                self.regex_query = theirs.regex_query;
+                self.test_string = theirs.test_string;
+                self.flags = theirs.flags;
+                self.examples = theirs.examples;
+                self.bench = theirs.bench;
+            }
+        }
+        // Their version is the one on disk now, which the next save may overwrite
+        self.disk = theirs.disk;
+        conflict
+    }
+
+    /// An empty session with the same name, directory and access as this one.
+    fn blank(&self) -> Self {
+        let name = match &self.name {
+            SessionName::Name(name) => SessionName::Name(name.clone()),
+            SessionName::Scratch => SessionName::Scratch,
+        };
+        Self {
+            name,
+            regex_query: Input::default(),
+            test_string: Input::default(),
+            flags: Flags::default(),
+            examples: Vec::new(),
+            bench: None,
+            workspace: None,
+            access: self.access,
+            dir: self.dir.clone(),
+            disk: None,
+            lock: None,
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            time: now(),
+            pattern: self.regex_query.string.clone(),
+            flags: self.flags.to_string(),
+            test_string: self.test_string.string.clone(),
+            examples: self.examples.clone(),
+        }
+    }
+
+    /// Replaces the content of the session with that of `snapshot`.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.regex_query.set(snapshot.pattern.clone());
+        self.test_string.set(snapshot.test_string.clone());
+        self.examples = snapshot.examples.clone();
+        // The flags were written by `Session::snapshot`, so they are always valid
+        self.flags = Flags::parse(&snapshot.flags).unwrap_or_default();
+    }
+
+    /// The saved versions of the session, oldest first. A scratch session has none.
+    pub fn history(&self) -> Vec<Snapshot> {
+        match &self.name {
+            SessionName::Name(name) => read_history(&history_path(&self.dir, name)),
+            SessionName::Scratch => Vec::new(),
+        }
+    }
+
+    /// Adds the session to its history, unless it's the same as the last version there.
+    fn record(&self, name: &str) -> io::Result<()> {
+        let path = history_path(&self.dir, name);
+        let mut snapshots = read_history(&path);
+        if snapshots.last().is_some_and(|last| last.matches(self)) {
+            return Ok(());
+        }
+        snapshots.push(self.snapshot());
+        let excess = snapshots.len().saturating_sub(HISTORY_LIMIT);
+        snapshots.drain(..excess);
+
+        // This unwrap is okay, because the history consists of nothing but strings,
+        // numbers and booleans
+        let content = toml::to_string(&History { snapshots }).unwrap();
+        write_atomic(&path, &content, false)
+    }
+
+    /// A self-contained copy of the session, which [`Session::import`] recreates.
+    pub fn export(&self) -> String {
+        let name = match &self.name {
+            SessionName::Name(name) => name.clone(),
+            SessionName::Scratch => String::new(),
+        };
+        let bundle = Bundle {
+            version: BUNDLE_VERSION,
+            name,
+            flavour: FLAVOUR.to_owned(),
+            pattern: self.regex_query.string.clone(),
+            flags: self.flags.to_string(),
+            test_string: self.test_string.string.clone(),
+            examples: self.examples.clone(),
+        };
+        // This unwrap is okay, because the bundle consists of nothing but strings,
+        // numbers and booleans
+        let bundle = toml::to_string(&bundle).unwrap();
+        format!("# replay session bundle, import it with `replay import <file>`\n{bundle}")
+    }
+
+    /// Recreates the session exported to `bundle`, under `name` if given, and otherwise
+    /// under the name it was exported with. It's an error if a different session by
+    /// that name exists, unless `force` is set.
+    pub fn import(
+        bundle: &str,
+        name: Option<String>,
+        force: bool,
+        config: &Config,
+    ) -> Result<Self, Error> {
+        let bundle: Bundle = toml::from_str(bundle).map_err(Error::InvalidBundle)?;
+        if bundle.version > BUNDLE_VERSION {
+            return Err(Error::BundleVersion(bundle.version));
+        }
+        if bundle.flavour != FLAVOUR {
+            return Err(Error::Flavour(bundle.flavour));
+        }
+        let flags = Flags::parse(&bundle.flags)
+            .ok_or_else(|| Error::InvalidFormat(FormatError::Entry("flags".to_owned())))?;
+        // Sessions are saved a line per field, so a line break would corrupt them
+        if bundle.pattern.contains(['\n', '\r']) {
+            return Err(Error::MultiLine("pattern"));
+        }
+        if bundle.test_string.contains(['\n', '\r']) {
+            return Err(Error::MultiLine("test string"));
+        }
+        if bundle
+            .examples
+            .iter()
+            .any(|e| e.text.contains(['\n', '\r']))
+        {
+            return Err(Error::MultiLine("example"));
         }
+
+        let name = name.unwrap_or(bundle.name);
+        let mut session = Self::fetch(name.clone(), config)?;
+        let exists = find_path(&session.dir, &name).exists();
+        let differs = session.regex_query.string != bundle.pattern
+            || session.test_string.string != bundle.test_string
+            || session.flags != flags
+            || session.examples != bundle.examples;
+        if exists && differs && !force {
+            return Err(Error::Conflict(name));
+        }
+
+        session.regex_query = Input::from(bundle.pattern);
+        session.test_string = Input::from(bundle.test_string);
+        session.flags = flags;
+        session.examples = bundle.examples;
+        // The benchmark results belong to the machine they were run on
+        session.bench = None;
+        Ok(session)
     }
 
-    pub fn save(&self) -> io::Result<()> {
+    /// The pattern as it's passed on to the regex engine, with the flags applied.
+    pub fn pattern(&self) -> Cow<'_, str> {
+        self.flags.apply(&self.regex_query.string)
+    }
+
+    /// Saves the session, unless it's read-only.
+    pub fn save(&mut self) -> io::Result<()> {
+        if self.access != Access::ReadWrite {
+            return Ok(());
+        }
         if let SessionName::Name(ref name) = self.name {
-            let path = get_path(name);
+            let path = get_path(&self.dir, name);
             if let Some(p) = path.parent() {
                 fs::create_dir_all(p)?;
             }
-            if self.regex_query.string.is_empty() && self.test_string.string.is_empty() {
+            if self.regex_query.string.is_empty()
+                && self.test_string.string.is_empty()
+                && self.examples.is_empty()
+            {
                 // If the session if empty - don't save it, and make sure that there
-                // is no file containing the previous snapshot of it.
-                fs::remove_file(path)
+                // is no file containing the previous snapshot of it. Its backup is kept,
+                // in case it was emptied by mistake.
+                remove_if_exists(&path)?;
+                if let Some(legacy) = legacy_path(&self.dir, name) {
+                    remove_if_exists(&legacy)?;
+                }
+                self.disk = None;
+                Ok(())
             } else {
-                fs::write(
-                    &path,
-                    format!(
-                        "{}:{}\n{}:{}",
-                        self.regex_query.cursor,
-                        self.regex_query.string,
-                        self.test_string.cursor,
-                        self.test_string.string
-                    ),
-                )
+                let content = self.serialize();
+                write_atomic(&path, &content, true)?;
+                self.record(name)?;
+                // The session is saved under its encoded name from now on
+                if let Some(legacy) = legacy_path(&self.dir, name) {
+                    remove_if_exists(&legacy)?;
+                }
+                self.disk = Some(content);
+                Ok(())
             }
         } else {
             Ok(())
         }
     }
+
+    /// The contents of the session's file, which [`parse_content`] reads back.
+    fn serialize(&self) -> String {
+        let mut content = format!(
+            "{}:{}\n{}:{}",
+            self.regex_query.cursor,
+            self.regex_query.string,
+            self.test_string.cursor,
+            self.test_string.string
+        );
+        if !self.flags.is_empty() {
+            content.push_str(&format!("\nflags:{}", self.flags));
+        }
+        for example in &self.examples {
+            let key = if example.should_match {
+                "match"
+            } else {
+                "no-match"
+            };
+            content.push_str(&format!("\n{key}:{}", example.text));
+        }
+        if let Some(bench) = self.bench {
+            content.push_str(&format!(
+                "\nbench:{} {} {} {} {} {}",
+                bench.compile.as_nanos(),
+                bench.captures_iter.as_nanos(),
+                bench.find_iter.as_nanos(),
+                bench.is_match.as_nanos(),
+                bench.bytes,
+                bench.iterations,
+            ));
+        }
+        if let Some(workspace) = self.workspace {
+            write_workspace(&mut content, &workspace);
+        }
+        content
+    }
+}
+
+/// A version of a session, recorded in its history each time it's saved.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Snapshot {
+    /// When it was saved, in seconds since the Unix epoch.
+    pub time: u64,
+    pub pattern: String,
+    #[serde(default)]
+    pub flags: String,
+    #[serde(default)]
+    pub test_string: String,
+    #[serde(default)]
+    pub examples: Vec<Example>,
+}
+
+impl Snapshot {
+    /// Whether `session` has the same content as the snapshot.
+    pub fn matches(&self, session: &Session) -> bool {
+        self.pattern == session.regex_query.string
+            && self.test_string == session.test_string.string
+            && self.flags == session.flags.to_string()
+            && self.examples == session.examples
+    }
+
+    /// How long ago the snapshot was taken, e.g. `5 min ago`.
+    pub fn age(&self) -> String {
+        match now().saturating_sub(self.time) {
+            0..60 => "just now".to_owned(),
+            secs @ 60..3600 => format!("{} min ago", secs / 60),
+            secs @ 3600..86400 => format!("{} h ago", secs / 3600),
+            secs => format!("{} days ago", secs / 86400),
+        }
+    }
+}
+
+/// The history file, as written.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct History {
+    #[serde(rename = "snapshot")]
+    snapshots: Vec<Snapshot>,
+}
+
+/// A session as it's exported, to be shared.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Bundle {
+    version: u32,
+    name: String,
+    flavour: String,
+    pattern: String,
+    #[serde(default)]
+    flags: String,
+    #[serde(default)]
+    test_string: String,
+    #[serde(default)]
+    examples: Vec<Example>,
 }
 
+/// Any name is allowed, since it's encoded before it's used as a file name, other
+/// than an empty one or one with control characters.
 fn validate_name(name: &str) -> Result<(), Error> {
-    if let Some(idx) = name.find(INVALID_CHARS) {
-        // This `unwrap` is okay, because we got that index from the `find`
-        // method, which means it must be in the bounds of the string
-        Err(Error::InvalidName(name.chars().nth(idx).unwrap()))
-    } else {
-        Ok(())
+    if name.is_empty() {
+        return Err(Error::EmptyName);
+    }
+    match name.chars().find(|ch| ch.is_control()) {
+        Some(ch) => Err(Error::InvalidName(ch)),
+        None => Ok(()),
+    }
+}
+
+/// Encodes `name` as a file name. Everything but ASCII letters, digits, `-` and `_` is
+/// percent-encoded, including `.`, so names such as `..` can't escape the sessions
+/// directory, and the extensions of the backup and temporary files can't clash with
+/// a session's name.
+fn encode_name(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// Decodes a file name made by [`encode_name`], returning `None` for anything else.
+fn decode_name(file_name: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(file_name.len());
+    let mut rest = file_name.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match byte {
+            b'%' => {
+                let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &tail[2..];
+            }
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => {
+                bytes.push(byte);
+                rest = tail;
+            }
+            _ => return None,
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// The saved session whose name equals `name` ignoring case, which would clash with it
+/// on case-insensitive file systems. There is no collision if `name` itself is saved,
+/// since the file system tells the sessions apart.
+fn find_collision(dir: &Path, name: &str) -> Option<String> {
+    let lowercase = name.to_lowercase();
+    let names: Vec<_> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| decode_name(entry.ok()?.file_name().to_str()?))
+        .collect();
+    if names.iter().any(|existing| existing == name) {
+        return None;
+    }
+    names
+        .into_iter()
+        .find(|existing| existing.to_lowercase() == lowercase)
+}
+
+/// Writes `content` to a temporary file and renames it over `path`, so a crash leaves
+/// either the old file or the new one in place, rather than a partly written one. With
+/// `backup`, the old file is kept as `<path>.bak`.
+pub(crate) fn write_atomic(path: &Path, content: &str, backup: bool) -> io::Result<()> {
+    let with_extension = |extension: &str| {
+        let mut file_name = path.file_name().unwrap_or_default().to_owned();
+        file_name.push(extension);
+        path.with_file_name(file_name)
+    };
+
+    let tmp = with_extension(".tmp");
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+
+    if backup {
+        match fs::copy(path, with_extension(".bak")) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+    fs::rename(&tmp, path)
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
     }
 }
 
-fn parse_session(path: &Path) -> Result<(Input, Input), Error> {
+/// Fills `session` from the file at `path`, if it exists.
+fn parse_session(path: &Path, session: &mut Session) -> Result<(), Error> {
     if let Ok(s) = fs::read_to_string(path) {
-        let lines: Vec<_> = s.split('\n').collect();
-        if lines.len() != 2 {
-            Err(Error::InvalidFormat(FormatError::Lines))
-        } else {
-            let regex_query = parse_field(lines[0])?;
-            let test_string = parse_field(lines[1])?;
-            Ok((regex_query, test_string))
+        parse_content(&s, session)?;
+        session.disk = Some(s);
+    }
+    // A blank session is used if the session file doesn't exist
+    Ok(())
+}
+
+/// Fills `session` from the contents of its file. The first two lines hold the fields,
+/// and the lines after them hold optional `<key>:<value>` entries.
+fn parse_content(s: &str, session: &mut Session) -> Result<(), Error> {
+    let lines: Vec<_> = s.split('\n').collect();
+    if lines.len() < 2 {
+        return Err(Error::InvalidFormat(FormatError::Lines));
+    }
+
+    session.regex_query = parse_field(lines[0])?;
+    session.test_string = parse_field(lines[1])?;
+    // The default flags are only for new sessions
+    session.flags = Flags::default();
+    session.examples.clear();
+
+    // Empty lines, e.g. a trailing new line added by an editor, are skipped
+    for line in lines[2..].iter().filter(|line| !line.is_empty()) {
+        let (key, value) = line
+            .split_once(':')
+            .ok_or(Error::InvalidFormat(FormatError::Separator))?;
+        let invalid = || Error::InvalidFormat(FormatError::Entry(key.to_owned()));
+        match key {
+            "flags" => session.flags = Flags::parse(value).ok_or_else(invalid)?,
+            "bench" => session.bench = Some(parse_bench(value).ok_or_else(invalid)?),
+            "match" | "no-match" => session.examples.push(Example {
+                text: value.to_owned(),
+                should_match: key == "match",
+            }),
+            "field" | "scroll" | "panels" | "code" | "selected" => {
+                let workspace = session.workspace.get_or_insert_default();
+                parse_workspace_entry(workspace, key, value).ok_or_else(invalid)?;
+            }
+            // Unknown entries are skipped, so older versions can open newer sessions
+            _ => {}
         }
-    } else {
-        // Create a blank session if the session file doesn't exist
-        Ok((Input::default(), Input::default()))
     }
+
+    // The entries may be stale, e.g. if the file was edited by hand
+    if let Some(workspace) = &mut session.workspace {
+        let hay = &session.test_string.string;
+        workspace.hay_scroll = workspace.hay_scroll.min(hay.chars().count());
+        workspace.selected = workspace
+            .selected
+            .filter(|&(_, start, end)| start <= end && end <= hay.len());
+    }
+    Ok(())
 }
 
-/// Creates a path to `~/.replay/persist/<name>`.
-fn get_path(name: &str) -> PathBuf {
-    let mut path = dirs::home_dir()
-        .with_context(|| "failed to get home dir")
-        .unwrap();
-    path.push(".replay");
-    path.push("persist");
-    path.push(name);
-    path
+/// Appends the entries of `workspace` that differ from the defaults to `content`.
+fn write_workspace(content: &mut String, workspace: &Workspace) {
+    if workspace.hay_focused {
+        content.push_str("\nfield:test");
+    }
+    if workspace.hay_scroll > 0 {
+        content.push_str(&format!("\nscroll:{}", workspace.hay_scroll));
+    }
+    let panels: Vec<_> = [
+        ("debugger", workspace.debugger),
+        ("bench", workspace.bench),
+        ("diagram", workspace.diagram),
+        ("debug", workspace.debug),
+    ]
+    .into_iter()
+    .filter_map(|(panel, open)| open.then_some(panel))
+    .collect();
+    if !panels.is_empty() {
+        content.push_str(&format!("\npanels:{}", panels.join(" ")));
+    }
+    if let Some(lang) = workspace.code {
+        content.push_str(&format!("\ncode:{}", lang.name()));
+    }
+    if let Some((group, start, end)) = workspace.selected {
+        content.push_str(&format!("\nselected:{group} {start} {end}"));
+    }
+}
+
+fn parse_workspace_entry(workspace: &mut Workspace, key: &str, value: &str) -> Option<()> {
+    match key {
+        "field" => workspace.hay_focused = value == "test",
+        "scroll" => workspace.hay_scroll = value.parse().ok()?,
+        "panels" => {
+            for panel in value.split(' ') {
+                match panel {
+                    "debugger" => workspace.debugger = true,
+                    "bench" => workspace.bench = true,
+                    "diagram" => workspace.diagram = true,
+                    "debug" => workspace.debug = true,
+                    // Panels added by newer versions are skipped
+                    _ => {}
+                }
+            }
+        }
+        "code" => workspace.code = Some(Language::from_name(value)?),
+        "selected" => {
+            let values = value
+                .split(' ')
+                .map(|v| v.parse().ok())
+                .collect::<Option<Vec<_>>>()?;
+            let [group, start, end] = values[..] else {
+                return None;
+            };
+            workspace.selected = Some((group, start, end));
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+fn parse_bench(s: &str) -> Option<Bench> {
+    let values = s
+        .split(' ')
+        .map(|v| v.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let [
+        compile,
+        captures_iter,
+        find_iter,
+        is_match,
+        bytes,
+        iterations,
+    ] = values[..]
+    else {
+        return None;
+    };
+    Some(Bench {
+        compile: Duration::from_nanos(compile),
+        captures_iter: Duration::from_nanos(captures_iter),
+        find_iter: Duration::from_nanos(find_iter),
+        is_match: Duration::from_nanos(is_match),
+        bytes: bytes.try_into().ok()?,
+        iterations: iterations.try_into().ok()?,
+    })
+}
+
+/// Creates a path to `<dir>/<encoded name>`.
+fn get_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(encode_name(name))
+}
+
+/// Creates a path to `<dir>/<encoded name>.history`.
+fn history_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.history", encode_name(name)))
+}
+
+/// Reads the history file at `path`. A history that can't be read is treated as an
+/// empty one, since losing it shouldn't stop the session from being saved.
+fn read_history(path: &Path) -> Vec<Snapshot> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| toml::from_str::<History>(&s).ok())
+        .map(|history| history.snapshots)
+        .unwrap_or_default()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// The path sessions were saved to before their names were encoded, `<dir>/<name>`,
+/// if the name was allowed back then. Names of files this version writes have none,
+/// e.g. `foo.bak` is the backup of `foo` rather than a session saved by an old version.
+fn legacy_path(dir: &Path, name: &str) -> Option<PathBuf> {
+    let mut components = Path::new(name).components();
+    let is_file_name = matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    );
+    // Older versions rejected names with spaces and slashes
+    let was_valid = !name.contains([' ', '/', '\\']);
+    let is_ours = decode_name(name).is_some()
+        || [".bak", ".history", ".lock", ".tmp"]
+            .iter()
+            .any(|extension| name.ends_with(extension));
+    (is_file_name && was_valid && !is_ours).then(|| dir.join(name))
+}
+
+/// The path the session named `name` is saved at, falling back to its legacy path
+/// if it was saved by an older version.
+fn find_path(dir: &Path, name: &str) -> PathBuf {
+    let path = get_path(dir, name);
+    match legacy_path(dir, name) {
+        Some(legacy) if !path.exists() && legacy.is_file() => legacy,
+        _ => path,
+    }
 }
 
 fn parse_field(s: &str) -> Result<Input, Error> {
     let (cursor, string) = s
         .split_once(':')
         .ok_or(Error::InvalidFormat(FormatError::Separator))?;
-    let cursor = cursor
+    let cursor: usize = cursor
         .parse()
         .map_err(|_| Error::InvalidFormat(FormatError::Cursor))?;
     Ok(Input {
         string: string.to_owned(),
-        cursor,
+        // A cursor past the end, e.g. from a file edited by hand, is moved to the end
+        cursor: cursor.min(string.chars().count()),
+        anchor: None,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session {
+        Session {
+            name: SessionName::Name("test".to_owned()),
+            regex_query: Input::default(),
+            test_string: Input::default(),
+            flags: Flags::default(),
+            examples: Vec::new(),
+            bench: None,
+            workspace: None,
+            access: Access::ReadWrite,
+            dir: PathBuf::new(),
+            disk: None,
+            lock: None,
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for name in [
+            "plain",
+            "with space",
+            "a/b",
+            "..",
+            "50%",
+            "ünïcödé",
+            "日本",
+            "x.bak",
+        ] {
+            let encoded = encode_name(name);
+            assert!(
+                encoded
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '%')),
+                "{encoded}"
+            );
+            assert_eq!(decode_name(&encoded).as_deref(), Some(name));
+        }
+        assert_eq!(encode_name("a/b"), "a%2Fb");
+        assert_eq!(encode_name("50%"), "50%25");
+    }
+
+    #[test]
+    fn file_names_that_are_not_encoded_names_are_rejected() {
+        // Other files kept next to sessions, and malformed or non-UTF-8 escapes
+        for file_name in ["test.bak", "test.history", "test.lock", "%2", "%zz", "%FF"] {
+            assert_eq!(decode_name(file_name), None, "{file_name}");
+        }
+    }
+
+    #[test]
+    fn content_round_trips() {
+        let mut saved = session();
+        saved.regex_query = Input {
+            string: r"(\d+)-ä".to_owned(),
+            cursor: 3,
+            anchor: None,
+        };
+        saved.test_string = Input {
+            string: "12-ä".to_owned(),
+            cursor: 4,
+            anchor: None,
+        };
+        saved.flags = Flags::parse("im").unwrap();
+        saved.examples = vec![
+            Example {
+                text: "1-ä".to_owned(),
+                should_match: true,
+            },
+            Example {
+                text: "key:value".to_owned(),
+                should_match: false,
+            },
+        ];
+        saved.workspace = Some(Workspace {
+            hay_focused: true,
+            hay_scroll: 2,
+            debugger: true,
+            code: Some(Language::Python),
+            selected: Some((1, 0, 2)),
+            ..Workspace::default()
+        });
+
+        let mut read = session();
+        parse_content(&saved.serialize(), &mut read).unwrap();
+        assert_eq!(read.regex_query.string, saved.regex_query.string);
+        assert_eq!(read.regex_query.cursor, 3);
+        assert_eq!(read.test_string.string, saved.test_string.string);
+        assert_eq!(read.test_string.cursor, 4);
+        assert!(read.flags == saved.flags);
+        assert!(read.examples == saved.examples);
+        let workspace = read.workspace.unwrap();
+        assert!(workspace.hay_focused && workspace.debugger && !workspace.bench);
+        assert_eq!(workspace.hay_scroll, 2);
+        assert!(workspace.code == Some(Language::Python));
+        assert_eq!(workspace.selected, Some((1, 0, 2)));
+    }
+
+    #[test]
+    fn examples_are_part_of_the_snapshots_and_bundles() {
+        let mut session = session();
+        let snapshot = session.snapshot();
+        session.examples.push(Example {
+            text: "a".to_owned(),
+            should_match: false,
+        });
+        // Otherwise an edit of only the examples wouldn't be saved
+        assert!(!snapshot.matches(&session));
+
+        let history = toml::to_string(&History {
+            snapshots: vec![session.snapshot()],
+        })
+        .unwrap();
+        let history: History = toml::from_str(&history).unwrap();
+        assert!(history.snapshots[0].examples == session.examples);
+        assert!(history.snapshots[0].matches(&session));
+
+        let bundle: Bundle = toml::from_str(&session.export()).unwrap();
+        assert!(bundle.examples == session.examples);
+    }
+
+    #[test]
+    fn stale_workspace_entries_are_clamped() {
+        let mut read = session();
+        parse_content("0:a\n0:abc\nscroll:10\nselected:0 1 9", &mut read).unwrap();
+        let workspace = read.workspace.unwrap();
+        assert_eq!(workspace.hay_scroll, 3);
+        assert_eq!(workspace.selected, None);
+    }
+
+    #[test]
+    fn invalid_content_is_rejected() {
+        let mut read = session();
+        assert!(parse_content("0:a", &mut read).is_err());
+        assert!(parse_content("x:a\n0:b", &mut read).is_err());
+        assert!(parse_content("0:a\n0:b\nflags:q", &mut read).is_err());
+        // Unknown entries are left for newer versions
+        assert!(parse_content("0:a\n0:b\nfuture:1", &mut read).is_ok());
+    }
+
+    #[test]
+    fn backups_are_not_mistaken_for_legacy_sessions() {
+        let dir = std::env::temp_dir().join(format!("replay-persist-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut foo = Session {
+            dir: dir.clone(),
+            name: SessionName::Name("foo".to_owned()),
+            ..session()
+        };
+        foo.regex_query.insert_str("a");
+        foo.save().unwrap();
+        foo.regex_query.insert_str("b");
+        foo.save().unwrap();
+        let backup = fs::read_to_string(dir.join("foo.bak")).unwrap();
+
+        // `foo.bak` is a session of its own, saved next to the backup of `foo`
+        let mut foo_bak = foo.open("foo.bak".to_owned()).unwrap();
+        assert_eq!(foo_bak.regex_query.string, "");
+        foo_bak.regex_query.insert_str("c");
+        foo_bak.save().unwrap();
+        assert_eq!(fs::read_to_string(dir.join("foo.bak")).unwrap(), backup);
+        assert_eq!(
+            foo.open("foo.bak".to_owned()).unwrap().regex_query.string,
+            "c"
+        );
+        assert_eq!(foo.open("foo".to_owned()).unwrap().regex_query.string, "ab");
+
+        // Sessions saved by old versions under their plain names are still found
+        fs::write(dir.join("x.y"), "1:xy\n0:").unwrap();
+        assert_eq!(foo.open("x.y".to_owned()).unwrap().regex_query.string, "xy");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
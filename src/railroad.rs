@@ -0,0 +1,333 @@
+use regex_syntax::ast::{
+    self, AssertionKind, Ast, ClassPerlKind, GroupKind, RepetitionKind, RepetitionRange,
+};
+
+use crate::{flags::Flags, lint::range};
+
+/// The part of the diagram in view, while its panel is open.
+#[derive(Default)]
+pub struct View {
+    /// How many rows the diagram is scrolled down by.
+    pub row: usize,
+    /// How many columns the diagram is scrolled to the left by.
+    pub col: usize,
+    /// The screen row the panel was last drawn at, to tell whether it's scrolled.
+    pub top: u16,
+}
+
+impl View {
+    pub fn scroll(&mut self, rows: isize, cols: isize) {
+        self.row = self.row.saturating_add_signed(rows);
+        self.col = self.col.saturating_add_signed(cols);
+    }
+}
+
+/// Draws `re` as a railroad diagram, as its lines, or returns `None` if it doesn't
+/// parse.
+pub fn diagram(re: &str, flags: Flags) -> Option<Vec<String>> {
+    let ast = ast::parse::ParserBuilder::new()
+        .ignore_whitespace(flags.ignore_whitespace)
+        .build()
+        .parse(re)
+        .ok()?;
+    let diagram = sequence(vec![
+        Block::text("●"),
+        block(re, &ast, flags),
+        Block::text("●"),
+    ]);
+    Some(diagram.lines)
+}
+
+/// A part of the diagram, which the track enters on the left and leaves on the right
+/// at the same row.
+struct Block {
+    lines: Vec<String>,
+    width: usize,
+    /// The row of the track.
+    track: usize,
+}
+
+impl Block {
+    fn text(s: &str) -> Self {
+        Self {
+            lines: vec![s.to_owned()],
+            width: s.chars().count(),
+            track: 0,
+        }
+    }
+
+    /// A box around `label`, with square corners for literals and round ones for
+    /// everything else.
+    fn terminal(label: &str, literal: bool) -> Self {
+        let [top_left, top_right, bottom_left, bottom_right] = if literal {
+            ['┌', '┐', '└', '┘']
+        } else {
+            ['╭', '╮', '╰', '╯']
+        };
+        let width = label.chars().count() + 2;
+        let bar = "─".repeat(width);
+        Self {
+            lines: vec![
+                format!("{top_left}{bar}{top_right}"),
+                format!("┤ {label} ├"),
+                format!("{bottom_left}{bar}{bottom_right}"),
+            ],
+            width: width + 2,
+            track: 1,
+        }
+    }
+
+    /// Adds `above` empty rows above, and as many below as make it `height` rows high.
+    fn pad(mut self, above: usize, height: usize) -> Self {
+        let blank = " ".repeat(self.width);
+        self.lines
+            .splice(0..0, std::iter::repeat_n(blank.clone(), above));
+        self.lines.resize(height, blank);
+        self.track += above;
+        self
+    }
+
+    /// Widens it to `width` columns, extending the track to the right.
+    fn widen(mut self, width: usize) -> Self {
+        let extra = width.saturating_sub(self.width);
+        for (i, line) in self.lines.iter_mut().enumerate() {
+            let fill = if i == self.track { '─' } else { ' ' };
+            line.extend(std::iter::repeat_n(fill, extra));
+        }
+        self.width = self.width.max(width);
+        self
+    }
+}
+
+fn block(re: &str, ast: &Ast, flags: Flags) -> Block {
+    match ast {
+        Ast::Empty(_) => Block::text("─"),
+        Ast::Literal(literal) => Block::terminal(&quote(&[literal.c]), true),
+        Ast::Dot(_) if flags.dot_matches_new_line => Block::terminal("any character", false),
+        Ast::Dot(_) => Block::terminal("any character but \\n", false),
+        Ast::Assertion(assertion) => {
+            let label = match assertion.kind {
+                // Without the `m` flag, they're the same as `\A` and `\z`
+                AssertionKind::StartLine if flags.multi_line => "start of line",
+                AssertionKind::EndLine if flags.multi_line => "end of line",
+                AssertionKind::StartLine | AssertionKind::StartText => "start of text",
+                AssertionKind::EndLine | AssertionKind::EndText => "end of text",
+                AssertionKind::WordBoundary => "word boundary",
+                AssertionKind::NotWordBoundary => "not a word boundary",
+                _ => &re[range(&assertion.span)],
+            };
+            Block::terminal(label, false)
+        }
+        Ast::ClassPerl(class) => {
+            let label = match class.kind {
+                ClassPerlKind::Digit => "digit",
+                ClassPerlKind::Space => "whitespace",
+                ClassPerlKind::Word => "word character",
+            };
+            let not = if class.negated { "not a " } else { "" };
+            Block::terminal(&format!("{not}{label}"), false)
+        }
+        Ast::ClassUnicode(class) => Block::terminal(&re[range(&class.span)], false),
+        Ast::ClassBracketed(class) => Block::terminal(&re[range(&class.span)], false),
+        Ast::Flags(set) => Block::terminal(&re[range(&set.span)], false),
+        Ast::Repetition(repetition) => {
+            let inner = block(re, &repetition.ast, flags);
+            let lazy = if repetition.greedy { "" } else { "lazy" };
+            let (min, count) = match &repetition.op.kind {
+                RepetitionKind::ZeroOrOne => return optional(inner),
+                RepetitionKind::ZeroOrMore => (0, String::new()),
+                RepetitionKind::OneOrMore => (1, String::new()),
+                RepetitionKind::Range(range) => {
+                    let (min, text) = match *range {
+                        RepetitionRange::Exactly(n) => (n, format!("{n}×")),
+                        RepetitionRange::AtLeast(n) => (n, format!("{n}+×")),
+                        RepetitionRange::Bounded(n, m) => (n, format!("{n}..{m}×")),
+                    };
+                    (min, text)
+                }
+            };
+            let label = [count.as_str(), lazy]
+                .into_iter()
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let looped = loop_back(inner, &label);
+            if min == 0 { optional(looped) } else { looped }
+        }
+        Ast::Group(group) => {
+            let inner = block(re, &group.ast, flags);
+            match &group.kind {
+                GroupKind::CaptureIndex(index) => frame(inner, &format!("group {index}")),
+                GroupKind::CaptureName { name, .. } => {
+                    frame(inner, &format!("group {} ({})", name.index, name.name))
+                }
+                GroupKind::NonCapturing(set) if set.items.is_empty() => inner,
+                GroupKind::NonCapturing(set) => {
+                    frame(inner, &format!("(?{}:)", &re[range(&set.span)]))
+                }
+            }
+        }
+        Ast::Alternation(alternation) => branches(
+            alternation
+                .asts
+                .iter()
+                .map(|ast| block(re, ast, flags))
+                .collect(),
+        ),
+        Ast::Concat(concat) => {
+            // Runs of literals are drawn as a single string
+            let mut blocks = Vec::new();
+            let mut literals = Vec::new();
+            for ast in &concat.asts {
+                if let Ast::Literal(literal) = ast {
+                    literals.push(literal.c);
+                    continue;
+                }
+                if !literals.is_empty() {
+                    blocks.push(Block::terminal(&quote(&literals), true));
+                    literals.clear();
+                }
+                blocks.push(block(re, ast, flags));
+            }
+            if !literals.is_empty() {
+                blocks.push(Block::terminal(&quote(&literals), true));
+            }
+            sequence(blocks)
+        }
+    }
+}
+
+fn quote(chars: &[char]) -> String {
+    let text: String = chars.iter().flat_map(|ch| ch.escape_debug()).collect();
+    format!("\"{text}\"")
+}
+
+/// The blocks one after the other, joined by the track.
+fn sequence(blocks: Vec<Block>) -> Block {
+    if blocks.is_empty() {
+        return Block::text("─");
+    }
+    let above = blocks.iter().map(|b| b.track).max().unwrap_or(0);
+    let below = blocks
+        .iter()
+        .map(|b| b.lines.len() - b.track - 1)
+        .max()
+        .unwrap_or(0);
+    let height = above + below + 1;
+    let width = blocks.iter().map(|b| b.width).sum::<usize>() + blocks.len() - 1;
+    let blocks: Vec<_> = blocks
+        .into_iter()
+        .map(|b| {
+            let above = above - b.track;
+            b.pad(above, height)
+        })
+        .collect();
+    let lines = (0..height)
+        .map(|row| {
+            let separator = if row == above { "─" } else { " " };
+            let parts: Vec<_> = blocks.iter().map(|b| b.lines[row].as_str()).collect();
+            parts.join(separator)
+        })
+        .collect();
+    Block {
+        lines,
+        width,
+        track: above,
+    }
+}
+
+/// The blocks stacked as alternative branches of the track.
+fn branches(blocks: Vec<Block>) -> Block {
+    let width = blocks.iter().map(|b| b.width).max().unwrap_or(0);
+    let mut lines = Vec::new();
+    let mut tracks = Vec::new();
+    for b in blocks {
+        let b = b.widen(width);
+        tracks.push(lines.len() + b.track);
+        lines.extend(b.lines);
+    }
+    let (first, last) = (tracks[0], tracks[tracks.len() - 1]);
+    let lines = lines
+        .into_iter()
+        .enumerate()
+        .map(|(row, line)| {
+            let (left, right) = if row == first {
+                ('┬', '┬')
+            } else if row == last {
+                ('╰', '╯')
+            } else if tracks.contains(&row) {
+                ('├', '┤')
+            } else if first < row && row < last {
+                ('│', '│')
+            } else {
+                (' ', ' ')
+            };
+            let fill = if tracks.contains(&row) { '─' } else { ' ' };
+            format!("{left}{fill}{line}{fill}{right}")
+        })
+        .collect();
+    Block {
+        lines,
+        width: width + 4,
+        track: first,
+    }
+}
+
+/// The block, or a track that skips it.
+fn optional(block: Block) -> Block {
+    branches(vec![Block::text("─"), block])
+}
+
+/// The block with a track under it that loops back to repeat it, labelled with how
+/// many times it does.
+fn loop_back(block: Block, label: &str) -> Block {
+    let label = if label.is_empty() {
+        String::new()
+    } else {
+        format!(" {label} ")
+    };
+    let block = block.widen(label.chars().count());
+    let width = block.width;
+    let track = block.track;
+    let mut lines: Vec<_> = block
+        .lines
+        .into_iter()
+        .enumerate()
+        .map(|(row, line)| {
+            let (rail, fill) = match row.cmp(&track) {
+                std::cmp::Ordering::Less => (' ', ' '),
+                std::cmp::Ordering::Equal => ('┬', '─'),
+                std::cmp::Ordering::Greater => ('│', ' '),
+            };
+            format!("{rail}{fill}{line}{fill}{rail}")
+        })
+        .collect();
+    lines.push(format!("╰{label:─^w$}╯", w = width + 2));
+    Block {
+        lines,
+        width: width + 4,
+        track,
+    }
+}
+
+/// The block in a dotted frame, labelled at its top.
+fn frame(block: Block, label: &str) -> Block {
+    let label = format!(" {label} ");
+    let block = block.widen(label.chars().count() + 1);
+    let width = block.width;
+    let track = block.track;
+    let mut lines = vec![format!("╭┄{label:┄<width$}┄╮")];
+    lines.extend(block.lines.into_iter().enumerate().map(|(row, line)| {
+        if row == track {
+            format!("──{line}──")
+        } else {
+            format!("┆ {line} ┆")
+        }
+    }));
+    lines.push(format!("╰{}╯", "┄".repeat(width + 2)));
+    Block {
+        lines,
+        width: width + 4,
+        track: track + 1,
+    }
+}
@@ -0,0 +1,165 @@
+use std::ops::Range;
+
+/// An entry of the regex quick reference.
+pub struct Entry {
+    pub category: &'static str,
+    /// The syntax, where `...` stands for whatever the user fills in.
+    pub token: &'static str,
+    pub description: &'static str,
+}
+
+impl Entry {
+    /// The text inserted into the pattern when the entry is picked, along with the
+    /// range of characters in it to fill in, which is selected so typing replaces it.
+    /// Classes can't be empty, so theirs are filled with `a-z`.
+    pub fn insert(&self) -> (String, Range<usize>) {
+        let fill = if self.token.starts_with('[') {
+            "a-z"
+        } else {
+            ""
+        };
+        let text = self.token.replace("...", fill);
+        let range = match self.token.find("...") {
+            Some(i) => {
+                let start = self.token[..i].chars().count();
+                start..start + fill.len()
+            }
+            None => {
+                let end = text.chars().count();
+                end..end
+            }
+        };
+        (text, range)
+    }
+}
+
+const fn entry(category: &'static str, token: &'static str, description: &'static str) -> Entry {
+    Entry {
+        category,
+        token,
+        description,
+    }
+}
+
+/// The syntax of Rust's regex crate, the only supported flavour.
+pub const REFERENCE: &[Entry] = &[
+    entry(
+        "class",
+        ".",
+        "any character except new line (with `s`, any character)",
+    ),
+    entry("class", r"\d", "digit (\\p{Nd})"),
+    entry("class", r"\D", "not a digit"),
+    entry(
+        "class",
+        r"\w",
+        "word character (\\p{Alphabetic} + \\p{M} + \\d + \\p{Pc} + \\p{Join_Control})",
+    ),
+    entry("class", r"\W", "not a word character"),
+    entry("class", r"\s", "whitespace (\\p{White_Space})"),
+    entry("class", r"\S", "not whitespace"),
+    entry("class", "[...]", "any of the characters in the brackets"),
+    entry("class", "[^...]", "none of the characters in the brackets"),
+    entry("class", "[a-z]", "a character in the range"),
+    entry(
+        "class",
+        "[[:alpha:]]",
+        "ASCII class (alnum, alpha, ascii, blank, cntrl, digit, graph, lower, print, punct, space, upper, word, xdigit)",
+    ),
+    entry("class", "[a-z&&[^aeiou]]", "intersection of classes"),
+    entry("class", "[a-z--aeiou]", "difference of classes"),
+    entry("class", "[a-g~~b-h]", "symmetric difference of classes"),
+    entry("anchor", "^", "start of the haystack (with `m`, of a line)"),
+    entry("anchor", "$", "end of the haystack (with `m`, of a line)"),
+    entry(
+        "anchor",
+        r"\A",
+        "start of the haystack, regardless of flags",
+    ),
+    entry("anchor", r"\z", "end of the haystack, regardless of flags"),
+    entry("anchor", r"\b", "word boundary"),
+    entry("anchor", r"\B", "not a word boundary"),
+    entry("anchor", r"\b{start}", "start of a word"),
+    entry("anchor", r"\b{end}", "end of a word"),
+    entry("quantifier", "*", "zero or more, greedy"),
+    entry("quantifier", "+", "one or more, greedy"),
+    entry("quantifier", "?", "zero or one, greedy"),
+    entry("quantifier", "{n}", "exactly n"),
+    entry("quantifier", "{n,}", "at least n, greedy"),
+    entry("quantifier", "{n,m}", "at least n and at most m, greedy"),
+    entry("quantifier", "*?", "zero or more, lazy"),
+    entry("quantifier", "+?", "one or more, lazy"),
+    entry("quantifier", "??", "zero or one, lazy"),
+    entry("quantifier", "{n,m}?", "at least n and at most m, lazy"),
+    entry("group", "(...)", "capture group"),
+    entry("group", "(?<name>...)", "named capture group"),
+    entry(
+        "group",
+        "(?P<name>...)",
+        "named capture group, Python's syntax",
+    ),
+    entry("group", "(?:...)", "non-capturing group"),
+    entry("group", "...|...", "either side"),
+    entry("flag", "(?i)", "case-insensitive"),
+    entry(
+        "flag",
+        "(?m)",
+        "multi-line, ^ and $ match at line boundaries",
+    ),
+    entry("flag", "(?s)", ". matches new line"),
+    entry("flag", "(?x)", "ignore whitespace and allow # comments"),
+    entry(
+        "flag",
+        "(?U)",
+        "swap the meaning of greedy and lazy quantifiers",
+    ),
+    entry("flag", "(?R)", "CRLF mode, \\r\\n counts as a line break"),
+    entry(
+        "flag",
+        "(?-u)",
+        "disable Unicode, classes match ASCII and bytes",
+    ),
+    entry("flag", "(?i:...)", "flags for a group only"),
+    entry("flag", "(?-i)", "disable a flag"),
+    entry("unicode", r"\p{L}", "letter (general category)"),
+    entry("unicode", r"\p{Lu}", "uppercase letter"),
+    entry("unicode", r"\p{Ll}", "lowercase letter"),
+    entry("unicode", r"\p{N}", "number"),
+    entry("unicode", r"\p{P}", "punctuation"),
+    entry("unicode", r"\p{Greek}", "script"),
+    entry("unicode", r"\p{Emoji}", "binary property"),
+    entry("unicode", r"\P{L}", "negated class, not a letter"),
+    entry("unicode", r"\pN", "one-letter class name"),
+    entry("escape", r"\n", "new line"),
+    entry("escape", r"\t", "tab"),
+    entry("escape", r"\x7F", "hex character code, two digits"),
+    entry(
+        "escape",
+        r"\u{1F600}",
+        "hex character code, any number of digits",
+    ),
+    entry("escape", r"\.", "literal, escaping a meta character"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_are_filled_validly() {
+        // The rest, such as quantifiers, are only valid after something
+        for entry in REFERENCE.iter().filter(|e| e.token.contains("...")) {
+            let (text, _) = entry.insert();
+            assert!(regex::Regex::new(&text).is_ok(), "{}: {text}", entry.token);
+        }
+    }
+
+    #[test]
+    fn placeholders_are_selected() {
+        let entry = |token| REFERENCE.iter().find(|e| e.token == token).unwrap();
+        assert_eq!(entry("[...]").insert(), ("[a-z]".to_owned(), 1..4));
+        assert_eq!(entry("[^...]").insert(), ("[^a-z]".to_owned(), 2..5));
+        assert_eq!(entry("(?:...)").insert(), ("(?:)".to_owned(), 3..3));
+        assert_eq!(entry(r"\d").insert(), (r"\d".to_owned(), 2..2));
+    }
+}
@@ -1,48 +1,514 @@
-use std::collections::HashMap;
+use std::{
+    mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+use crate::lru::{self, Lru};
+
+/// The span a capture group matched, where group 0 is the whole match.
+#[derive(Clone, Copy)]
+pub struct Capture {
+    pub group: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+pub type Matches = Vec<Vec<Capture>>;
+
+/// How long to wait for a fresh job before reporting it as pending, so fast
+/// patterns don't flash a "compiling…" state on every keystroke.
+pub(crate) const GRACE: Duration = Duration::from_millis(20);
+
+/// How many background jobs may run at once. Jobs that run out of time keep running
+/// until they finish, so this stops them from piling up.
+const MAX_RUNNING: usize = 8;
+
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+/// A place among the [`MAX_RUNNING`] background jobs, given back when dropped.
+struct Running;
+
+impl Running {
+    fn reserve() -> Option<Self> {
+        RUNNING
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |running| {
+                (running < MAX_RUNNING).then_some(running + 1)
+            })
+            .ok()
+            .map(|_| Self)
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        RUNNING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// How many entries the regex cache keeps around.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Capacity {
+    /// Compiled patterns.
+    pub regexes: usize,
+    /// Haystacks whose captures are remembered, per compiled pattern.
+    pub haystacks: usize,
+}
+
+impl Default for Capacity {
+    fn default() -> Self {
+        Self {
+            regexes: 64,
+            haystacks: 16,
+        }
+    }
+}
+
+/// Protects the UI from catastrophic patterns.
+#[derive(Clone, Copy)]
+pub struct Limits {
+    /// See [`RegexBuilder::size_limit`].
+    pub size_limit: usize,
+    /// See [`RegexBuilder::dfa_size_limit`].
+    pub dfa_size_limit: usize,
+    /// How long compiling a pattern, or matching it against a haystack, may take.
+    pub time_budget: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            size_limit: 10 * (1 << 20),
+            dfa_size_limit: 2 * (1 << 20),
+            time_budget: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0}")]
+    Regex(#[from] regex::Error),
+    #[error("the background job stopped unexpectedly")]
+    Lost,
+}
+
+pub enum Status<'a> {
+    Compiling,
+    Matching,
+    /// Compiling or matching ran out of time, but is still going on in the background.
+    TimedOut(Duration),
+    Ready(&'a Matches),
+    Failed(&'a Error),
+}
+
+/// Lookups are only counted when they're for something else than the last one, so
+/// redraws that change nothing don't count.
+#[derive(Default, Clone, Copy)]
+pub struct Stats {
+    pub regex_hits: u64,
+    pub regex_misses: u64,
+    pub captures_hits: u64,
+    pub captures_misses: u64,
+    pub evictions: u64,
+    pub regexes: usize,
+    pub haystacks: usize,
+    /// Rough estimate of the heap memory used by the cached entries, in bytes.
+    pub memory: usize,
+}
+
+type Job<T> = Box<dyn FnOnce() -> Result<T, Error> + Send>;
+
+/// The result of a job running on a background thread.
+pub(crate) enum Slot<T> {
+    /// Waiting for a place among the running jobs.
+    Queued(Job<T>),
+    Pending {
+        rx: Receiver<Result<T, Error>>,
+        started: Instant,
+        /// Whether the job has run out of time. It's kept around in case it finishes
+        /// after all.
+        timed_out: bool,
+    },
+    Done(Result<T, Error>),
+}
+
+impl<T: Send + 'static> Slot<T> {
+    pub(crate) fn spawn(job: impl FnOnce() -> Result<T, Error> + Send + 'static) -> Self {
+        let mut slot = Self::Queued(Box::new(job));
+        slot.start();
+        slot
+    }
+
+    /// Starts the job if it's queued and there's room for it.
+    fn start(&mut self) {
+        if !matches!(self, Self::Queued(_)) {
+            return;
+        }
+        let Some(running) = Running::reserve() else {
+            return;
+        };
+        let (tx, rx) = mpsc::channel();
+        let pending = Self::Pending {
+            rx,
+            started: Instant::now(),
+            timed_out: false,
+        };
+        let Self::Queued(job) = mem::replace(self, pending) else {
+            unreachable!("the slot was checked to be queued above");
+        };
+        // A job that runs out of time is abandoned rather than killed. The regex crate
+        // guarantees linear time matching and bounded compilation, so it finishes eventually.
+        thread::spawn(move || {
+            let _running = running;
+            tx.send(job())
+        });
+    }
+
+    /// Waits up to `timeout` for the job, returning whether the slot has changed.
+    pub(crate) fn wait(&mut self, timeout: Duration, budget: Duration) -> bool {
+        self.start();
+        let Self::Pending {
+            rx,
+            started,
+            timed_out,
+        } = self
+        else {
+            return false;
+        };
+        let timeout = if *timed_out {
+            timeout
+        } else {
+            timeout.min(budget.saturating_sub(started.elapsed()))
+        };
+        let result = match rx.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => {
+                let changed = !*timed_out && started.elapsed() >= budget;
+                *timed_out |= changed;
+                return changed;
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(Error::Lost),
+        };
+        *self = Self::Done(result);
+        true
+    }
+
+    /// Whether the job is queued or still within its time budget.
+    pub(crate) fn running(&self) -> bool {
+        matches!(
+            self,
+            Self::Queued(_)
+                | Self::Pending {
+                    timed_out: false,
+                    ..
+                }
+        )
+    }
+
+    pub(crate) fn done(&self) -> Option<&T> {
+        match self {
+            Self::Done(Ok(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn done_mut(&mut self) -> Option<&mut T> {
+        match self {
+            Self::Done(Ok(value)) => Some(value),
+            _ => None,
+        }
+    }
+}
 
 struct CapturesCache {
-    cache: HashMap<String, Vec<Vec<(usize, usize)>>>,
+    cache: Lru<Slot<Matches>>,
 }
 
 impl CapturesCache {
-    pub fn new() -> Self {
+    pub fn new(capacity: usize) -> Self {
         Self {
-            cache: HashMap::new(),
+            cache: Lru::new(capacity),
         }
     }
 
-    pub fn get_or_init(&mut self, re: &Regex, hay: &str) -> &Vec<Vec<(usize, usize)>> {
-        self.cache.entry(hay.to_owned()).or_insert_with(|| {
-            re.captures_iter(hay)
-                .map(|c| c.iter().flatten().map(|m| (m.start(), m.end())).collect())
-                .collect()
-        })
+    pub fn get_or_init(&mut self, re: &Regex, hay: &str, budget: Duration) -> Status<'_> {
+        let key = lru::hash(hay);
+        if !self.cache.contains(key) {
+            let (re, hay) = (re.clone(), hay.to_owned());
+            let slot = self.cache.insert(
+                key,
+                Slot::spawn(move || {
+                    Ok(re
+                        .captures_iter(&hay)
+                        .map(|c| {
+                            c.iter()
+                                .enumerate()
+                                .filter_map(|(group, m)| {
+                                    m.map(|m| Capture {
+                                        group,
+                                        start: m.start(),
+                                        end: m.end(),
+                                    })
+                                })
+                                .collect()
+                        })
+                        .collect())
+                }),
+            );
+            slot.wait(GRACE, budget);
+        }
+
+        // This `unwrap` is okay, because we made sure the entry exists above
+        match self.cache.get_mut(key).unwrap() {
+            slot if slot.running() => Status::Matching,
+            Slot::Queued(_) | Slot::Pending { .. } => Status::TimedOut(budget),
+            Slot::Done(Ok(matches)) => Status::Ready(matches),
+            Slot::Done(Err(err)) => Status::Failed(err),
+        }
+    }
+
+    fn poll(&mut self, budget: Duration) -> bool {
+        let mut changed = false;
+        for slot in self.cache.values_mut() {
+            changed |= slot.wait(Duration::ZERO, budget);
+        }
+        changed
+    }
+
+    fn memory(&self) -> usize {
+        self.cache
+            .values()
+            .filter_map(Slot::done)
+            .map(|matches| {
+                mem::size_of_val(matches.as_slice())
+                    + matches
+                        .iter()
+                        .map(|m| mem::size_of_val(m.as_slice()))
+                        .sum::<usize>()
+            })
+            .sum()
+    }
+}
+
+/// Counts a lookup of `key` as a hit if it was `cached`, or else as a miss, unless the
+/// last lookup was of the same key.
+fn count<K: PartialEq>(
+    last: &mut Option<K>,
+    key: K,
+    cached: bool,
+    (hits, misses): (&mut u64, &mut u64),
+) {
+    if last.as_ref() == Some(&key) {
+        return;
+    }
+    *last = Some(key);
+    if cached {
+        *hits += 1;
+    } else {
+        *misses += 1;
     }
 }
 
 pub struct Cache {
-    cache: HashMap<String, Result<(Regex, CapturesCache), regex::Error>>,
+    cache: Lru<Slot<(Regex, CapturesCache)>>,
+    capacity: Capacity,
+    limits: Limits,
+    stats: Stats,
+    /// The keys of the last lookups, which repeats of aren't counted.
+    last_regex: Option<u64>,
+    last_captures: Option<(u64, u64)>,
 }
 
 impl Cache {
-    pub fn new() -> Self {
+    pub fn new(capacity: Capacity, limits: Limits) -> Self {
         Self {
-            cache: HashMap::new(),
+            cache: Lru::new(capacity.regexes),
+            capacity,
+            limits,
+            stats: Stats::default(),
+            last_regex: None,
+            last_captures: None,
+        }
+    }
+
+    /// Compiles `re` and matches it against `hay` in the background, reporting
+    /// the progress made so far.
+    pub fn get_or_init(&mut self, re: &str, hay: &str) -> Status<'_> {
+        let key = lru::hash(re);
+        let cached = self.start(re);
+        let stats = &mut self.stats;
+        count(
+            &mut self.last_regex,
+            key,
+            cached,
+            (&mut stats.regex_hits, &mut stats.regex_misses),
+        );
+
+        let time_budget = self.limits.time_budget;
+        // This `unwrap` is okay, because we made sure the entry exists above
+        match self.cache.get_mut(key).unwrap() {
+            slot if slot.running() => Status::Compiling,
+            Slot::Queued(_) | Slot::Pending { .. } => Status::TimedOut(time_budget),
+            Slot::Done(Ok((r, c))) => {
+                let hay_key = lru::hash(hay);
+                count(
+                    &mut self.last_captures,
+                    (key, hay_key),
+                    c.cache.contains(hay_key),
+                    (&mut stats.captures_hits, &mut stats.captures_misses),
+                );
+                c.get_or_init(r, hay, time_budget)
+            }
+            Slot::Done(Err(err)) => Status::Failed(err),
+        }
+    }
+
+    /// Compiles `re` in the background, returning it if it has compiled successfully.
+    pub fn compile(&mut self, re: &str) -> Option<&Regex> {
+        let cached = self.start(re);
+        let stats = &mut self.stats;
+        count(
+            &mut self.last_regex,
+            lru::hash(re),
+            cached,
+            (&mut stats.regex_hits, &mut stats.regex_misses),
+        );
+        self.compiled(re)
+    }
+
+    /// Starts compiling `re` in the background, unless it's cached. Returns whether
+    /// it was.
+    fn start(&mut self, re: &str) -> bool {
+        let Limits {
+            size_limit,
+            dfa_size_limit,
+            time_budget,
+        } = self.limits;
+
+        let key = lru::hash(re);
+        if self.cache.contains(key) {
+            return true;
+        }
+        let (re, haystacks) = (re.to_owned(), self.capacity.haystacks);
+        let slot = self.cache.insert(
+            key,
+            Slot::spawn(move || {
+                let re = RegexBuilder::new(&re)
+                    .size_limit(size_limit)
+                    .dfa_size_limit(dfa_size_limit)
+                    .build()?;
+                Ok((re, CapturesCache::new(haystacks)))
+            }),
+        );
+        slot.wait(GRACE, time_budget);
+        false
+    }
+
+    /// Checks on the background jobs, returning whether any of them has finished
+    /// or ran out of time since the last call.
+    pub fn poll(&mut self) -> bool {
+        let budget = self.limits.time_budget;
+        let mut changed = false;
+        for slot in self.cache.values_mut() {
+            changed |= slot.wait(Duration::ZERO, budget);
+            if let Slot::Done(Ok((_, c))) = slot {
+                changed |= c.poll(budget);
+            }
+        }
+        changed
+    }
+
+    pub fn stats(&self) -> Stats {
+        let compiled = || self.cache.values().filter_map(Slot::done);
+        Stats {
+            evictions: self.cache.evictions()
+                + compiled().map(|(_, c)| c.cache.evictions()).sum::<u64>(),
+            regexes: self.cache.len(),
+            haystacks: compiled().map(|(_, c)| c.cache.len()).sum(),
+            memory: compiled().map(|(r, c)| r.as_str().len() + c.memory()).sum(),
+            ..self.stats
         }
     }
 
-    pub fn get_or_init(
-        &mut self,
-        re: &str,
-        hay: &str,
-    ) -> Result<&Vec<Vec<(usize, usize)>>, &regex::Error> {
+    /// Returns the matches of `re` in `hay`, if they have already been found. Unlike
+    /// [`Cache::get_or_init`], it starts no work and isn't counted as a lookup.
+    pub fn matched(&mut self, re: &str, hay: &str) -> Option<&Matches> {
+        let (_, captures) = self.cache.get_mut(lru::hash(re))?.done_mut()?;
+        captures.cache.get_mut(lru::hash(hay))?.done()
+    }
+
+    /// Returns the compiled pattern, if it has already been compiled successfully.
+    pub fn compiled(&mut self, re: &str) -> Option<&Regex> {
         self.cache
-            .entry(re.to_owned())
-            .or_insert_with(|| Regex::new(re).map(|r| (r, CapturesCache::new())))
-            .as_mut()
-            .map(|(r, c)| c.get_or_init(r, hay))
-            .map_err(|err| &*err)
+            .get_mut(lru::hash(re))
+            .and_then(|slot| slot.done())
+            .map(|(r, _)| r)
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn capacity(&self) -> &Capacity {
+        &self.capacity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Looks `re` up until it has been matched against `hay`.
+    fn settle(cache: &mut Cache, re: &str, hay: &str) {
+        while let Status::Compiling | Status::Matching = cache.get_or_init(re, hay) {
+            thread::sleep(GRACE);
+            cache.poll();
+        }
+    }
+
+    #[test]
+    fn timed_out_jobs_can_still_finish() {
+        let budget = Duration::from_millis(10);
+        let mut slot = Slot::spawn(|| {
+            thread::sleep(Duration::from_millis(50));
+            Ok(1)
+        });
+        assert!(slot.wait(budget, budget));
+        assert!(!slot.running());
+        assert!(slot.done().is_none());
+
+        while !slot.wait(GRACE, budget) {}
+        assert_eq!(slot.done(), Some(&1));
+    }
+
+    #[test]
+    fn repeated_lookups_count_once() {
+        let mut cache = Cache::new(Capacity::default(), Limits::default());
+        settle(&mut cache, "a+", "aa");
+        settle(&mut cache, "a+", "aa");
+        let stats = cache.stats();
+        assert_eq!((stats.regex_hits, stats.regex_misses), (0, 1));
+        assert_eq!((stats.captures_hits, stats.captures_misses), (0, 1));
+
+        settle(&mut cache, "a+", "b");
+        settle(&mut cache, "a+", "aa");
+        assert!(cache.compile("a+").is_some());
+        let stats = cache.stats();
+        assert_eq!((stats.regex_hits, stats.regex_misses), (0, 1));
+        assert_eq!((stats.captures_hits, stats.captures_misses), (1, 2));
+
+        settle(&mut cache, "b", "aa");
+        settle(&mut cache, "a+", "aa");
+        let stats = cache.stats();
+        assert_eq!((stats.regex_hits, stats.regex_misses), (1, 2));
     }
 }
@@ -1,30 +1,68 @@
-use std::{cmp, fmt::Display, io};
+use std::{cmp, fmt::Display, io, ops::Range};
 
 use crossterm::{
     Command,
     cursor::MoveTo,
     queue,
-    style::{Color, Print, SetForegroundColor},
+    style::{
+        Attribute, Color, ContentStyle, Print, ResetColor, SetAttribute, SetForegroundColor,
+        SetStyle,
+    },
     terminal::{Clear, ClearType},
 };
 
-use crate::{Group, LAYER_COLORS, highlight::HighlightEventWrapper};
+use crate::{
+    Group,
+    debugger::{Report, StepKind},
+    diagnostic::Diagnostic,
+    highlight::{self, HighlightEventWrapper, Structure},
+    regex::{Capture, Matches},
+    theme::Theme,
+};
+
+/// How the matches in the test string are emphasized.
+#[derive(Clone, Copy)]
+pub enum Emphasis {
+    None,
+    /// All matches are faint, e.g. when they belong to a previous pattern.
+    Dimmed,
+    /// Only the given capture group stands out, and the rest are faint.
+    Group(usize),
+}
 
-pub struct Render<W: io::Write>(W);
+pub struct Render<W: io::Write> {
+    w: W,
+    /// The lowest row drawn on since the screen was last cleared.
+    bottom: u16,
+    /// How many characters the test string is scrolled to the left by.
+    scroll: usize,
+    theme: Theme,
+}
 
 impl<W: io::Write> Render<W> {
-    pub fn new(w: W) -> Self {
-        Self(w)
+    pub fn new(w: W, theme: Theme) -> Self {
+        Self {
+            w,
+            bottom: 0,
+            scroll: 0,
+            theme,
+        }
+    }
+
+    #[inline]
+    pub fn theme(&self) -> &Theme {
+        &self.theme
     }
 
     #[inline]
     pub fn queue(&mut self, command: impl Command) -> io::Result<()> {
-        queue!(self.0, command)
+        queue!(self.w, command)
     }
 
     #[inline]
     pub fn clear(&mut self) -> io::Result<()> {
-        queue!(self.0, Clear(ClearType::All))
+        self.bottom = 0;
+        queue!(self.w, Clear(ClearType::All))
     }
 
     #[inline]
@@ -32,12 +70,13 @@ impl<W: io::Write> Render<W> {
     where
         T: Display,
     {
-        queue!(self.0, SetForegroundColor(color), Print(text))
+        queue!(self.w, SetForegroundColor(color), Print(text))
     }
 
     #[inline]
     pub fn move_to(&mut self, col: u16, row: u16) -> io::Result<()> {
-        queue!(self.0, MoveTo(col, row))
+        self.bottom = cmp::max(self.bottom, row);
+        queue!(self.w, MoveTo(col, row))
     }
 
     pub fn at<T>(&mut self, color: Color, text: T, col: u16, row: u16) -> io::Result<()>
@@ -48,52 +87,186 @@ impl<W: io::Write> Render<W> {
         self.draw(color, text)
     }
 
+    #[inline]
+    pub fn bottom(&self) -> u16 {
+        self.bottom
+    }
+
     #[inline]
     pub fn flush(&mut self) -> io::Result<()> {
-        self.0.flush()
+        self.w.flush()
+    }
+
+    #[inline]
+    pub fn set_scroll(&mut self, scroll: usize) {
+        self.scroll = scroll;
+    }
+
+    /// Draws `text` at character `at` of the test string, clipping whatever is scrolled
+    /// out of view to the left of `col`.
+    fn put(
+        &mut self,
+        style: ContentStyle,
+        text: &str,
+        at: usize,
+        col: u16,
+        row: u16,
+    ) -> io::Result<()> {
+        let skip = self.scroll.saturating_sub(at);
+        let visible: String = text.chars().skip(skip).collect();
+        if visible.is_empty() {
+            return Ok(());
+        }
+        self.move_to(col + (at + skip - self.scroll) as u16, row)?;
+        queue!(
+            self.w,
+            SetStyle(style),
+            Print(visible),
+            SetAttribute(Attribute::Reset),
+            ResetColor
+        )
     }
 
-    pub fn draw_regex_query(&mut self, s: &str, col: u16, row: u16) -> io::Result<()> {
+    #[inline]
+    pub fn draw_with<T>(&mut self, color: Color, attribute: Attribute, text: T) -> io::Result<()>
+    where
+        T: Display,
+    {
+        queue!(
+            self.w,
+            SetAttribute(attribute),
+            SetForegroundColor(color),
+            Print(text),
+            SetAttribute(Attribute::Reset)
+        )
+    }
+
+    /// Draws the pattern with syntax highlighting, coloring each group by its depth and
+    /// annotating capturing groups with their index on the row above.
+    ///
+    /// `cursor` is the character the cursor is on, if the pattern is being edited; when
+    /// it's on a group's parenthesis, both parentheses of the group are highlighted.
+    /// When the pattern is `invalid`, the parts of it that fail to parse are marked.
+    /// The capture groups in `lit` are underlined.
+    pub fn draw_regex_query(
+        &mut self,
+        s: &str,
+        cursor: Option<usize>,
+        invalid: bool,
+        lit: &[usize],
+        col: u16,
+        row: u16,
+    ) -> io::Result<()> {
+        let Structure { groups, errors } = highlight::structure(s);
+        let cursor = cursor.and_then(|cursor| s.char_indices().nth(cursor).map(|(i, _)| i));
+        let matching = groups
+            .iter()
+            .find(|g| cursor.is_some_and(|c| c == g.open || Some(c) == g.close));
+
         self.move_to(col, row)?;
-        let mut layer = 0;
         let mut syntax_highlighting = HighlightEventWrapper::new(s.as_bytes()).unwrap_or_default();
-        for ch in s.chars() {
+        for (idx, ch) in s.char_indices() {
             let syntax_color = syntax_highlighting
                 .by_ref()
                 .take(ch.len_utf8())
                 .last()
-                .unwrap_or(Color::Reset);
+                .flatten()
+                .map_or(Color::Reset, |group| self.theme.syntax(group));
 
-            let color = match ch {
-                '(' => {
-                    layer += 1;
-                    LAYER_COLORS[layer]
+            let is_lit = groups.iter().any(|g| {
+                g.index.is_some_and(|index| lit.contains(&index))
+                    && g.open <= idx
+                    && g.close.is_none_or(|close| idx <= close)
+            });
+            match groups
+                .iter()
+                .find(|g| g.open == idx || g.close == Some(idx))
+            {
+                Some(group) => {
+                    let attribute = if matching.is_some_and(|m| m.open == group.open) {
+                        Attribute::Reverse
+                    } else if is_lit {
+                        Attribute::Underlined
+                    } else {
+                        Attribute::Bold
+                    };
+                    self.draw_with(self.theme.layer(group.depth), attribute, ch)?;
                 }
-                ')' => {
-                    let color = LAYER_COLORS[layer];
-                    layer = layer.saturating_sub(1);
-                    color
+                None if invalid && errors.iter().any(|e| e.contains(&idx)) => {
+                    self.draw_with(self.theme.error, Attribute::Underlined, ch)?
                 }
-                _ => syntax_color,
-            };
-            self.draw(color, ch)?;
+                None if is_lit => self.draw_with(syntax_color, Attribute::Underlined, ch)?,
+                None => self.draw(syntax_color, ch)?,
+            }
+        }
+
+        for group in &groups {
+            if let Some(index) = group.index {
+                let offset = s[..group.open].chars().count() as u16;
+                self.at(self.theme.layer(group.depth), index, col + offset, row - 1)?;
+            }
         }
 
         Ok(())
     }
 
+    /// Underlines the offending part of the pattern, with the diagnostic's message
+    /// after it.
+    pub fn draw_diagnostic(
+        &mut self,
+        s: &str,
+        diagnostic: &Diagnostic,
+        color: Color,
+        col: u16,
+        row: u16,
+    ) -> io::Result<()> {
+        let Some(span) = &diagnostic.span else {
+            return self.at(color, &diagnostic.message, col, row);
+        };
+
+        let offset = |idx: usize| s.get(..idx).map_or(0, |s| s.chars().count()) as u16;
+        let (start, end) = (offset(span.start), offset(span.end));
+        self.move_to(col + start, row)?;
+        self.draw(color, '^')?;
+        for _ in start + 1..end {
+            self.draw(color, '~')?;
+        }
+        self.draw(color, format_args!(" {}", diagnostic.message))
+    }
+
+    /// Draws the test string colored by the matches.
     pub fn draw_regex_hay(
         &mut self,
         s: &str,
-        matches: &Vec<Vec<(usize, usize)>>,
+        matches: &Matches,
+        emphasis: Emphasis,
         col: u16,
         row: u16,
     ) -> io::Result<()> {
-        self.at(Color::Reset, s, col, row)?;
+        self.draw_scrolled(s, col, row)?;
 
+        let theme = self.theme.clone();
+        let style = |layer, group| {
+            let mut style = ContentStyle::new();
+            match emphasis {
+                Emphasis::Dimmed => style.foreground_color = Some(theme.muted),
+                Emphasis::Group(emphasized) if group != emphasized => {
+                    style.foreground_color = Some(theme.muted)
+                }
+                Emphasis::None | Emphasis::Group(_) => {
+                    style.foreground_color = Some(theme.layer(layer));
+                    style.background_color = theme.background(layer);
+                    if let Emphasis::Group(_) = emphasis {
+                        style.attributes.set(Attribute::Underlined);
+                    }
+                }
+            }
+            style
+        };
         for captures in matches {
-            let (max_layer, infos) = self.draw_regex_match(s, captures, col, row)?;
-            self.draw_regex_groups(&infos, col, row, max_layer)?;
+            let (max_layer, infos) = layout(s, captures);
+            self.draw_regex_match(s, &infos, style, col, row)?;
+            self.draw_regex_groups(&infos, style, col, row, max_layer)?;
         }
 
         Ok(())
@@ -102,64 +275,285 @@ impl<W: io::Write> Render<W> {
     fn draw_regex_match(
         &mut self,
         s: &str,
-        captures: &Vec<(usize, usize)>,
+        infos: &[Group],
+        style: impl Fn(usize, usize) -> ContentStyle,
         col: u16,
         row: u16,
-    ) -> io::Result<(usize, Vec<Group>)> {
-        let mut layers = Vec::new();
-        let mut infos = Vec::new();
-        let mut max_layer = 0;
-
-        for &(start, end) in captures {
-            while layers.last().is_some_and(|l| *l <= start) {
-                layers.pop();
-            }
-            layers.push(end);
-
-            let color = LAYER_COLORS[layers.len() - 1];
-
-            self.at(color, &s[start..end], col + start as u16, row)?;
-
-            let layer = layers.len() - 1;
-            infos.push(Group { start, end, layer });
-
-            max_layer = cmp::max(max_layer, layer);
+    ) -> io::Result<()> {
+        for info in infos {
+            let (start, end) = (byte_at(s, info.start), byte_at(s, info.end));
+            self.put(
+                style(info.layer, info.group),
+                &s[start..end],
+                info.start,
+                col,
+                row,
+            )?;
         }
 
-        Ok((max_layer, infos))
+        Ok(())
     }
 
     fn draw_regex_groups(
         &mut self,
         infos: &[Group],
+        style: impl Fn(usize, usize) -> ContentStyle,
         col: u16,
         row: u16,
         max_layer: usize,
     ) -> Result<(), io::Error> {
-        for &Group { start, end, layer } in infos {
-            let color = LAYER_COLORS[layer];
-            let (start, end, layer) = (start as u16, end as u16, layer as u16);
-            let max_layer = max_layer as u16;
+        for &Group {
+            start,
+            end,
+            layer,
+            group,
+        } in infos
+        {
+            // The markers below the test string only take the foreground color
+            let style = ContentStyle {
+                foreground_color: style(layer, group).foreground_color,
+                ..ContentStyle::new()
+            };
+            let underline_row = row + layer as u16 + 1;
+            let width = end.saturating_sub(start);
 
-            for idx in start..end.saturating_sub(1) {
-                self.at(color, '~', col + idx, row + layer + 1)?;
-            }
-            self.at(color, '|', col + end.saturating_sub(1), row + layer + 1)?;
+            let underline = "~".repeat(width.saturating_sub(1));
+            self.put(style, &underline, start, col, underline_row)?;
+            self.put(style, "|", end.saturating_sub(1), col, underline_row)?;
 
             for line in layer + 1..=max_layer + 1 {
-                self.at(color, '|', col + start, row + line)?;
+                self.put(style, "|", start, col, row + line as u16)?;
             }
-            self.at(color, layer, col + start, row + max_layer + 2)?;
+            let label = layer.to_string();
+            self.put(style, &label, start, col, row + max_layer as u16 + 2)?;
         }
 
         Ok(())
     }
 
+    /// Draws the test string as is, scrolled.
+    pub fn draw_scrolled(&mut self, s: &str, col: u16, row: u16) -> io::Result<()> {
+        self.put(ContentStyle::new(), s, 0, col, row)
+    }
+
+    /// Reverses the colors of the `selected` characters of a field, which is scrolled
+    /// `skip` characters to the left.
+    pub fn draw_selection(
+        &mut self,
+        s: &str,
+        selected: Range<usize>,
+        skip: usize,
+        col: u16,
+        row: u16,
+    ) -> io::Result<()> {
+        let start = selected.start.max(skip);
+        let text: String = s.chars().take(selected.end).skip(start).collect();
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.move_to(col + (start - skip) as u16, row)?;
+        self.draw_with(Color::Reset, Attribute::Reverse, text)
+    }
+
+    /// Draws the test string without any matches, with a note on why they are missing.
+    pub fn draw_status(&mut self, s: &str, status: &str, col: u16, row: u16) -> io::Result<()> {
+        self.draw_scrolled(s, col, row)?;
+        self.at(self.theme.muted, status, col, row + 1)
+    }
+
+    /// Draws a titled panel, with its lines starting at `col`.
+    pub fn draw_panel<T: Display>(
+        &mut self,
+        title: &str,
+        lines: impl IntoIterator<Item = T>,
+        col: u16,
+        row: u16,
+    ) -> io::Result<()> {
+        self.at(Color::Reset, title, 0, row)?;
+        for (i, line) in lines.into_iter().enumerate() {
+            self.at(Color::Reset, line, col, row + 1 + i as u16)?;
+        }
+        Ok(())
+    }
+
+    /// Draws a box of `width` columns over whatever was drawn below it, with a search
+    /// field next to its title and the `selected` entry highlighted.
+    pub fn draw_overlay(
+        &mut self,
+        title: &str,
+        query: &str,
+        entries: &[String],
+        selected: Option<usize>,
+        row: u16,
+        width: usize,
+    ) -> io::Result<()> {
+        let fit = |text: &str| {
+            let text: String = text.chars().take(width).collect();
+            format!("{text:<width$}")
+        };
+
+        self.move_to(0, row)?;
+        self.draw_with(
+            Color::Reset,
+            Attribute::Reverse,
+            fit(&format!("{title} {query}")),
+        )?;
+        for (i, entry) in entries.iter().enumerate() {
+            let attribute = if selected == Some(i) {
+                Attribute::Reverse
+            } else {
+                Attribute::Reset
+            };
+            self.move_to(0, row + 1 + i as u16)?;
+            self.draw_with(Color::Reset, attribute, fit(entry))?;
+        }
+        if entries.is_empty() {
+            self.move_to(0, row + 1)?;
+            self.draw(self.theme.muted, fit("no matches"))?;
+        }
+        Ok(())
+    }
+
+    /// Copies `text` to the system clipboard through the terminal (OSC 52).
+    pub fn copy(&mut self, text: &str) -> io::Result<()> {
+        write!(self.w, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+        self.w.flush()
+    }
+
     pub fn draw_error(&mut self, s: &str, col: u16, row: u16) -> io::Result<()> {
-        self.at(Color::DarkRed, "ERROR", col, row)?;
+        self.at(self.theme.error, "ERROR", col, row)?;
         for (i, line) in s.lines().enumerate() {
             self.at(Color::Reset, line, col, row + 1 + i as u16)?
         }
         Ok(())
     }
+
+    pub fn draw_debugger(
+        &mut self,
+        report: &Report,
+        step: usize,
+        hay: &str,
+        col: u16,
+        row: u16,
+    ) -> io::Result<()> {
+        self.at(
+            Color::Reset,
+            format_args!(
+                "DEBUGGER  find: lazy DFA, captures: {} | NFA states: {} | DFA states: {} (~{} KiB)",
+                report.engine,
+                report.nfa_states,
+                report.dfa_states,
+                report.dfa_memory.div_ceil(1024),
+            ),
+            0,
+            row,
+        )?;
+
+        let Some(current) = report.steps.get(step) else {
+            return self.at(self.theme.muted, "no steps to trace", col, row + 1);
+        };
+
+        // The test string is scrolled like it is above, so the caret is at the
+        // character the byte is part of
+        self.draw_scrolled(hay, col, row + 1)?;
+        let at = match hay.get(..current.offset) {
+            Some(before) => before.chars().count(),
+            // A byte in the middle of a character
+            None => {
+                hay.char_indices()
+                    .take_while(|&(i, _)| i < current.offset)
+                    .count()
+                    - 1
+            }
+        };
+        let caret = ContentStyle {
+            foreground_color: Some(self.theme.accent),
+            ..ContentStyle::new()
+        };
+        self.put(caret, "^", at, col, row + 2)?;
+
+        let byte = match current.byte {
+            Some(byte) if byte.is_ascii() => format!("byte {:?}", byte as char),
+            Some(byte) => format!("byte 0x{byte:02x}"),
+            None => "end of input".to_owned(),
+        };
+        let (color, note) = match current.kind {
+            StepKind::Normal => (Color::Reset, String::new()),
+            StepKind::Match => (
+                self.theme.success,
+                format!(" (match ends at {})", current.offset),
+            ),
+            StepKind::Dead => (self.theme.error, " (dead, no more matches)".to_owned()),
+            StepKind::Quit => (self.theme.error, " (gave up)".to_owned()),
+        };
+        self.at(
+            color,
+            format_args!(
+                "step {}/{}, search from {}: {} at {} -> state #{}{}",
+                step + 1,
+                report.steps.len(),
+                current.start,
+                byte,
+                current.offset,
+                current.state,
+                note,
+            ),
+            col,
+            row + 3,
+        )
+    }
+}
+
+/// Stacks the captures of a match into layers by nesting, the way they are drawn, and
+/// returns them along with the deepest layer. Their spans are converted to character
+/// offsets into `s`.
+pub fn layout(s: &str, captures: &[Capture]) -> (usize, Vec<Group>) {
+    let mut layers = Vec::new();
+    let mut infos = Vec::new();
+    let mut max_layer = 0;
+
+    for &Capture { group, start, end } in captures {
+        while layers.last().is_some_and(|l| *l <= start) {
+            layers.pop();
+        }
+        layers.push(end);
+
+        let layer = layers.len() - 1;
+        let offset = |idx: usize| s.get(..idx).map_or(0, |s| s.chars().count());
+        infos.push(Group {
+            start: offset(start),
+            end: offset(end),
+            layer,
+            group,
+        });
+
+        max_layer = cmp::max(max_layer, layer);
+    }
+
+    (max_layer, infos)
+}
+
+/// The byte index of the character at `idx`.
+fn byte_at(s: &str, idx: usize) -> usize {
+    s.char_indices().nth(idx).map_or(s.len(), |(i, _)| i)
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
 }
@@ -0,0 +1,299 @@
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+use regex_syntax::ast::{self, Ast, ClassPerlKind, ClassSet, ClassSetItem, GroupKind, LiteralKind};
+
+use crate::{
+    flags::Flags,
+    lint::{Fix, Lint, range},
+    regex::Limits,
+};
+
+/// Suggests rewrites that make `re` simpler, as lints with the rewritten pattern as
+/// their fix. Only the rewrites that find the same matches in `hay` as `regex`, its
+/// compiled form, with the same groups capturing the same spans, are suggested, apart
+/// from the group a rewrite drops. The rewritten patterns are compiled within `limits`,
+/// but the caller has to enforce its time budget.
+pub fn suggestions(re: &str, flags: Flags, regex: &Regex, hay: &str, limits: &Limits) -> Vec<Lint> {
+    let Ok(ast) = ast::parse::ParserBuilder::new()
+        .ignore_whitespace(flags.ignore_whitespace)
+        .build()
+        .parse(re)
+    else {
+        return Vec::new();
+    };
+
+    let mut rewrites = Vec::new();
+    visit(re, &ast, &mut rewrites);
+
+    let captures = |regex: &Regex, dropped: Option<usize>| -> Vec<Vec<_>> {
+        regex
+            .captures_iter(hay)
+            .map(|caps| {
+                caps.iter()
+                    .enumerate()
+                    .filter(|(group, _)| Some(*group) != dropped)
+                    .map(|(_, m)| m.map(|m| m.range()))
+                    .collect()
+            })
+            .collect()
+    };
+    rewrites
+        .into_iter()
+        .filter_map(|rewrite| {
+            let mut pattern = re.to_owned();
+            pattern.replace_range(rewrite.span.clone(), &rewrite.text);
+            let compiled = RegexBuilder::new(&flags.apply(&pattern))
+                .size_limit(limits.size_limit)
+                .dfa_size_limit(limits.dfa_size_limit)
+                .build()
+                .ok()?;
+            let groups = regex.captures_len() - usize::from(rewrite.dropped.is_some());
+            let same = compiled.captures_len() == groups
+                && captures(&compiled, None) == captures(regex, rewrite.dropped);
+            same.then(|| Lint {
+                message: format!("`{}` {}", &re[rewrite.span.clone()], rewrite.reason),
+                fix: Some(Fix {
+                    description: format!("rewrite it as `{}`", rewrite.text),
+                    pattern,
+                    flags,
+                }),
+                span: rewrite.span,
+            })
+        })
+        .collect()
+}
+
+/// The replacement of a part of the pattern.
+struct Rewrite {
+    span: Range<usize>,
+    text: String,
+    /// Why it's simpler, after the part that is replaced.
+    reason: &'static str,
+    /// The capture group the rewrite drops, if any.
+    dropped: Option<usize>,
+}
+
+fn visit(re: &str, ast: &Ast, rewrites: &mut Vec<Rewrite>) {
+    match ast {
+        Ast::Concat(concat) => {
+            repeated_literals(re, &concat.asts, rewrites);
+            concat.asts.iter().for_each(|ast| visit(re, ast, rewrites));
+        }
+        Ast::Alternation(alternation) => {
+            // Alternatives of single characters are left to the group around them,
+            // which the class replaces when it doesn't capture
+            if !alternation.asts.iter().all(is_char) {
+                common_prefix(re, alternation, rewrites);
+            } else if alternation.span.start.offset == 0 && alternation.span.end.offset == re.len()
+            {
+                rewrites.push(Rewrite {
+                    span: range(&alternation.span),
+                    text: class_of(re, &alternation.asts),
+                    reason: "is shorter as a class",
+                    dropped: None,
+                });
+            }
+            alternation
+                .asts
+                .iter()
+                .for_each(|ast| visit(re, ast, rewrites));
+        }
+        Ast::Group(group) => {
+            let span = range(&group.span);
+            // The capture can't be referred to by a name, so it's likely only there to
+            // group what's in it
+            if let GroupKind::CaptureIndex(index) = group.kind {
+                rewrites.push(Rewrite {
+                    span: span.clone(),
+                    text: format!("(?:{})", &re[range(group.ast.span())]),
+                    reason: "captures without a name, which may not be needed",
+                    dropped: Some(index as usize),
+                });
+            }
+            match (&group.kind, &*group.ast) {
+                (GroupKind::NonCapturing(flags), Ast::Alternation(alternation))
+                    if flags.items.is_empty() && alternation.asts.iter().all(is_char) =>
+                {
+                    rewrites.push(Rewrite {
+                        span: span.clone(),
+                        text: class_of(re, &alternation.asts),
+                        reason: "is shorter as a class",
+                        dropped: None,
+                    });
+                }
+                (GroupKind::CaptureIndex(_), Ast::Alternation(alternation))
+                    if alternation.asts.iter().all(is_char) =>
+                {
+                    rewrites.push(Rewrite {
+                        span: span.clone(),
+                        text: format!("({})", class_of(re, &alternation.asts)),
+                        reason: "is shorter as a class",
+                        dropped: None,
+                    });
+                }
+                _ => {}
+            }
+            visit(re, &group.ast, rewrites);
+        }
+        Ast::Repetition(repetition) => visit(re, &repetition.ast, rewrites),
+        Ast::ClassBracketed(class) => {
+            if let ClassSet::Item(ClassSetItem::Range(r)) = &class.kind
+                && (r.start.c, r.end.c) == ('0', '9')
+            {
+                rewrites.push(Rewrite {
+                    span: range(&class.span),
+                    text: if class.negated { r"\D" } else { r"\d" }.to_owned(),
+                    reason: "is shorter as a Perl class, which also matches other scripts' digits",
+                    dropped: None,
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `aaa`, which can be written as `a{3}`.
+fn repeated_literals(re: &str, asts: &[Ast], rewrites: &mut Vec<Rewrite>) {
+    let mut i = 0;
+    while i < asts.len() {
+        let Ast::Literal(literal) = &asts[i] else {
+            i += 1;
+            continue;
+        };
+        let text = &re[range(&literal.span)];
+        let run = asts[i..]
+            .iter()
+            .take_while(
+                |ast| matches!(ast, Ast::Literal(l) if l.c == literal.c && l.kind == literal.kind),
+            )
+            .count();
+        if run >= 3 {
+            rewrites.push(Rewrite {
+                span: literal.span.start.offset..asts[i + run - 1].span().end.offset,
+                text: format!("{text}{{{run}}}"),
+                reason: "is shorter as a counted repetition",
+                dropped: None,
+            });
+        }
+        i += run;
+    }
+}
+
+/// `foo|fob`, which can be written as `fo(?:o|b)`.
+fn common_prefix(re: &str, alternation: &ast::Alternation, rewrites: &mut Vec<Rewrite>) {
+    let branches: Vec<&[Ast]> = alternation
+        .asts
+        .iter()
+        .map(|ast| match ast {
+            Ast::Concat(concat) => &concat.asts[..],
+            ast => std::slice::from_ref(ast),
+        })
+        .collect();
+    let text = |ast: &Ast| &re[range(ast.span())];
+
+    // Factoring out groups would change how many captures there are
+    let first = branches[0];
+    let len = (0..first.len())
+        .take_while(|&i| {
+            !has_group(&first[i])
+                && branches[1..].iter().all(|branch| {
+                    branch
+                        .get(i)
+                        .is_some_and(|ast| text(ast) == text(&first[i]))
+                })
+        })
+        .count();
+    // Leave something in each alternative, so that no empty alternative is made
+    if len == 0 || branches.iter().any(|branch| branch.len() == len) {
+        return;
+    }
+
+    let prefix = range(first[0].span()).start..range(first[len - 1].span()).end;
+    let rests: Vec<_> = branches
+        .iter()
+        .map(|branch| {
+            &re[branch[len].span().start.offset..branch[branch.len() - 1].span().end.offset]
+        })
+        .collect();
+    rewrites.push(Rewrite {
+        span: range(&alternation.span),
+        text: format!("{}(?:{})", &re[prefix], rests.join("|")),
+        reason: "repeats the same prefix in each alternative",
+        dropped: None,
+    });
+}
+
+/// The class matching the characters `asts` each match.
+fn class_of(re: &str, asts: &[Ast]) -> String {
+    let chars: String = asts
+        .iter()
+        .map(|ast| match ast {
+            Ast::Literal(literal)
+                if literal.kind == LiteralKind::Verbatim
+                    && regex_syntax::is_meta_character(literal.c) =>
+            {
+                format!("\\{}", literal.c)
+            }
+            ast => re[range(ast.span())].to_owned(),
+        })
+        .collect();
+    format!("[{chars}]")
+}
+
+/// Whether `ast` is a single character, which can be put in a class as is.
+fn is_char(ast: &Ast) -> bool {
+    match ast {
+        Ast::Literal(_) => true,
+        Ast::ClassPerl(class) => matches!(
+            class.kind,
+            ClassPerlKind::Digit | ClassPerlKind::Space | ClassPerlKind::Word
+        ),
+        _ => false,
+    }
+}
+
+fn has_group(ast: &Ast) -> bool {
+    match ast {
+        Ast::Group(_) => true,
+        Ast::Repetition(repetition) => has_group(&repetition.ast),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrites(re: &str, hay: &str) -> Vec<String> {
+        let regex = Regex::new(re).unwrap();
+        suggestions(re, Flags::default(), &regex, hay, &Limits::default())
+            .into_iter()
+            .filter_map(|lint| Some(lint.fix?.pattern))
+            .collect()
+    }
+
+    #[test]
+    fn rewrites_keep_the_captures() {
+        assert_eq!(rewrites("(a|b|c)x", "bx"), ["(?:a|b|c)x", "([abc])x"]);
+        assert_eq!(rewrites("x(?:a|b)", "xb"), ["x[ab]"]);
+        assert_eq!(rewrites("aaab", "aaab"), ["a{3}b"]);
+        assert_eq!(rewrites("foo|fob", "fob"), ["fo(?:o|b)"]);
+    }
+
+    #[test]
+    fn unnamed_groups_can_be_dropped() {
+        assert_eq!(rewrites("(ab)c", "abc"), ["(?:ab)c"]);
+        // The groups after the dropped one are renumbered
+        assert_eq!(rewrites("(a)(b)", "ab"), ["(?:a)(b)", "(a)(?:b)"]);
+        assert!(rewrites("(?P<n>ab)c", "abc").is_empty());
+    }
+
+    #[test]
+    fn digit_ranges_are_checked_against_the_test_string() {
+        assert_eq!(rewrites("[0-9]+", "123"), [r"\d+"]);
+        assert_eq!(rewrites("[^0-9]", "a1"), [r"\D"]);
+        // `\d` also matches other scripts' digits, such as the Arabic-Indic ones
+        assert!(rewrites("[0-9]+", "12 \u{661}\u{662}").is_empty());
+    }
+}
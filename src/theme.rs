@@ -0,0 +1,296 @@
+use crossterm::style::Color;
+use serde::{Deserialize, Deserializer, de};
+
+use crate::highlight::HighlightGroup;
+
+/// The colors everything is drawn with.
+#[derive(Clone)]
+pub struct Theme {
+    /// The colors of the matches' nesting layers, the first one being the match
+    /// itself. Deeper layers wrap around the rest.
+    pub layers: Vec<Color>,
+    /// The background colors of the layers, the same way. Empty for no backgrounds.
+    pub backgrounds: Vec<Color>,
+    pub flags: Color,
+    pub anchors: Color,
+    pub quantifiers: Color,
+    pub character_class: Color,
+    pub operator: Color,
+    pub escape: Color,
+    pub group: Color,
+    /// Secondary text, e.g. the session name, statuses and dimmed matches.
+    pub muted: Color,
+    pub error: Color,
+    /// Markers pointing at something, e.g. the debugger's position.
+    pub accent: Color,
+    pub success: Color,
+}
+
+impl Theme {
+    pub const NAMES: [&str; 3] = ["dark", "light", "high-contrast"];
+
+    pub fn dark() -> Self {
+        Self {
+            layers: vec![
+                Color::Grey,
+                Color::Green,
+                Color::Yellow,
+                Color::Blue,
+                Color::Magenta,
+                Color::Cyan,
+            ],
+            backgrounds: Vec::new(),
+            flags: Color::Blue,
+            anchors: Color::Magenta,
+            quantifiers: Color::DarkMagenta,
+            character_class: Color::DarkBlue,
+            operator: Color::DarkYellow,
+            escape: Color::Cyan,
+            group: Color::DarkCyan,
+            muted: Color::DarkGrey,
+            error: Color::DarkRed,
+            accent: Color::Yellow,
+            success: Color::Green,
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            layers: vec![
+                Color::Black,
+                Color::DarkGreen,
+                Color::DarkYellow,
+                Color::DarkBlue,
+                Color::DarkMagenta,
+                Color::DarkCyan,
+            ],
+            backgrounds: Vec::new(),
+            flags: Color::DarkBlue,
+            anchors: Color::DarkMagenta,
+            quantifiers: Color::Magenta,
+            character_class: Color::Blue,
+            operator: Color::DarkYellow,
+            escape: Color::DarkCyan,
+            group: Color::DarkGreen,
+            muted: Color::Grey,
+            error: Color::DarkRed,
+            accent: Color::DarkYellow,
+            success: Color::DarkGreen,
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Self {
+            layers: vec![Color::Black; 6],
+            backgrounds: vec![
+                Color::White,
+                Color::Green,
+                Color::Yellow,
+                Color::Cyan,
+                Color::Magenta,
+                Color::Rgb {
+                    r: 255,
+                    g: 165,
+                    b: 0,
+                },
+            ],
+            flags: Color::Cyan,
+            anchors: Color::Magenta,
+            quantifiers: Color::Yellow,
+            character_class: Color::Green,
+            operator: Color::Yellow,
+            escape: Color::Cyan,
+            group: Color::White,
+            muted: Color::Grey,
+            error: Color::Red,
+            accent: Color::Yellow,
+            success: Color::Green,
+        }
+    }
+
+    /// No colors at all, for when `NO_COLOR` is set. Matches are still told apart by
+    /// their underlines.
+    pub fn plain() -> Self {
+        Self {
+            layers: vec![Color::Reset],
+            backgrounds: Vec::new(),
+            flags: Color::Reset,
+            anchors: Color::Reset,
+            quantifiers: Color::Reset,
+            character_class: Color::Reset,
+            operator: Color::Reset,
+            escape: Color::Reset,
+            group: Color::Reset,
+            muted: Color::Reset,
+            error: Color::Reset,
+            accent: Color::Reset,
+            success: Color::Reset,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// The color of a nesting layer, where layer 0 is the match itself.
+    pub fn layer(&self, layer: usize) -> Color {
+        wrap(&self.layers, layer).unwrap_or(Color::Reset)
+    }
+
+    /// The background color of a nesting layer, if the theme has any.
+    pub fn background(&self, layer: usize) -> Option<Color> {
+        wrap(&self.backgrounds, layer)
+    }
+
+    pub fn syntax(&self, group: HighlightGroup) -> Color {
+        match group {
+            HighlightGroup::Flags => self.flags,
+            HighlightGroup::Anchors => self.anchors,
+            HighlightGroup::Quantifiers => self.quantifiers,
+            HighlightGroup::CharacterClass => self.character_class,
+            HighlightGroup::Operator => self.operator,
+            HighlightGroup::Escape => self.escape,
+            HighlightGroup::Group => self.group,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+/// Layer 0 has its own color, and the deeper layers cycle through the rest.
+fn wrap(colors: &[Color], layer: usize) -> Option<Color> {
+    match (layer, colors.len()) {
+        (_, 0) => None,
+        (0, _) | (_, 1) => Some(colors[0]),
+        (layer, len) => Some(colors[1 + (layer - 1) % (len - 1)]),
+    }
+}
+
+/// The `[theme]` table of the config file: a built-in theme to start from, and the
+/// colors to override in it.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    #[serde(deserialize_with = "base")]
+    base: Option<Theme>,
+    #[serde(deserialize_with = "colors")]
+    layers: Option<Vec<Color>>,
+    #[serde(deserialize_with = "colors")]
+    backgrounds: Option<Vec<Color>>,
+    #[serde(deserialize_with = "color")]
+    flags: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    anchors: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    quantifiers: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    character_class: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    operator: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    escape: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    group: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    muted: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    error: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    accent: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    success: Option<Color>,
+}
+
+impl ThemeConfig {
+    pub fn build(self) -> Theme {
+        let mut theme = self.base.unwrap_or_default();
+        let overrides = [
+            (self.flags, &mut theme.flags),
+            (self.anchors, &mut theme.anchors),
+            (self.quantifiers, &mut theme.quantifiers),
+            (self.character_class, &mut theme.character_class),
+            (self.operator, &mut theme.operator),
+            (self.escape, &mut theme.escape),
+            (self.group, &mut theme.group),
+            (self.muted, &mut theme.muted),
+            (self.error, &mut theme.error),
+            (self.accent, &mut theme.accent),
+            (self.success, &mut theme.success),
+        ];
+        for (color, slot) in overrides {
+            if let Some(color) = color {
+                *slot = color;
+            }
+        }
+        if let Some(layers) = self.layers {
+            theme.layers = layers;
+        }
+        if let Some(backgrounds) = self.backgrounds {
+            theme.backgrounds = backgrounds;
+        }
+        theme
+    }
+}
+
+/// A color is written as a name (`"dark_green"`), a hex RGB string (`"#ff8800"`) or an
+/// ANSI 256-color palette index (`208`).
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Ansi(u8),
+    Text(String),
+}
+
+impl ColorValue {
+    fn parse<E: de::Error>(self) -> Result<Color, E> {
+        let s = match self {
+            Self::Ansi(value) => return Ok(Color::AnsiValue(value)),
+            Self::Text(s) => s,
+        };
+        if let Some(hex) = s.strip_prefix('#') {
+            let channel = |i: usize| {
+                hex.get(i..i + 2)
+                    .and_then(|c| u8::from_str_radix(c, 16).ok())
+            };
+            return match (hex.len(), channel(0), channel(2), channel(4)) {
+                (6, Some(r), Some(g), Some(b)) => Ok(Color::Rgb { r, g, b }),
+                _ => Err(E::custom(format!("invalid hex color `{s}`"))),
+            };
+        }
+        match s.as_str() {
+            "reset" | "default" => Ok(Color::Reset),
+            name => Color::try_from(name).map_err(|_| E::custom(format!("unknown color `{name}`"))),
+        }
+    }
+}
+
+fn base<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Theme>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    Theme::from_name(&name).map(Some).ok_or_else(|| {
+        de::Error::custom(format!(
+            "unknown theme `{name}`, expected one of: {}",
+            Theme::NAMES.join(", ")
+        ))
+    })
+}
+
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Color>, D::Error> {
+    ColorValue::deserialize(deserializer)?.parse().map(Some)
+}
+
+fn colors<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<Color>>, D::Error> {
+    Vec::<ColorValue>::deserialize(deserializer)?
+        .into_iter()
+        .map(ColorValue::parse)
+        .collect::<Result<_, _>>()
+        .map(Some)
+}